@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use ffav::easy::{SimpleWriter, VideoDesc, Writer};
+
+#[allow(dead_code)]
+#[path = "../src/easy/fixture.rs"]
+mod fixture;
+
+fn open(path: &str, format: Option<&str>, interleaved: bool, auto_flush: bool) -> SimpleWriter {
+    let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+    let mut writer = SimpleWriter::new(path, &[&v_desc], format, None).unwrap();
+    writer.set_interleaved(interleaved);
+    writer.set_auto_flush(auto_flush);
+    writer
+}
+
+fn mux(writer: &mut SimpleWriter, frames: &[&[u8]]) {
+    let mut pts = 0;
+    for frame in frames {
+        writer.write_bytes(frame, pts, 40000, false, 0).unwrap();
+        pts += 40000;
+    }
+}
+
+fn bench_muxing(c: &mut Criterion) {
+    let frames: Vec<&[u8]> = fixture::example_frames().collect();
+    let total_bytes: usize = frames.iter().map(|x| x.len()).sum();
+    let mut group = c.benchmark_group("muxing");
+    group.throughput(Throughput::Bytes(total_bytes as u64));
+    for &(name, path, format) in [
+        ("mp4", "/tmp/ffav-bench.mp4", None),
+        ("mpegts", "/tmp/ffav-bench.ts", Some("mpegts")),
+    ]
+    .iter()
+    {
+        for &interleaved in [true, false].iter() {
+            for &auto_flush in [true, false].iter() {
+                let id = format!(
+                    "{}/{}/{}",
+                    name,
+                    if interleaved { "interleaved" } else { "direct" },
+                    if auto_flush { "auto-flush" } else { "no-flush" }
+                );
+                // Only the writing is timed, the trailer is written on dropping
+                // the writer returned.
+                group.bench_function(id, |b| {
+                    b.iter_batched(
+                        || open(path, format, interleaved, auto_flush),
+                        |mut writer| {
+                            mux(&mut writer, &frames);
+                            writer
+                        },
+                        BatchSize::PerIteration,
+                    )
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_muxing);
+criterion_main!(benches);
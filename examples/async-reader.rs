@@ -0,0 +1,15 @@
+use ffav::easy::AsyncReader;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut reader = AsyncReader::open("/tmp/envivio-352x288.264.mp4", None, None).await?;
+    let mut num_frames = 0;
+    let mut num_bytes = 0;
+    while let Some(frame) = reader.read_frame().await {
+        num_frames += 1;
+        num_bytes += frame.data().len();
+    }
+    println!("frames={}, bytes={}", num_frames, num_bytes);
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use ffav::easy::{OpenOptions, VideoDesc};
+use std::convert::TryInto;
+
+/// Returns `true` if the H264 frame contains an IDR slice.
+fn is_key_frame(bytes: &[u8]) -> bool {
+    bytes
+        .windows(4)
+        .any(|x| x[0..3] == [0, 0, 1] && (x[3] & 0x1f) == 5)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let output_path = "/tmp/ffav-hls";
+    std::fs::create_dir_all(output_path)?;
+
+    // The hls muxer writes the playlist and the segments by itself.
+    let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+    let mut writer = OpenOptions::new()
+        .media(v_desc)
+        .format("hls")
+        .format_options(format!(
+            "hls_time=2:hls_list_size=0:hls_segment_filename={}/seg%03d.ts",
+            output_path
+        ))
+        .open(format!("{}/index.m3u8", output_path))?;
+
+    let example_bytes = include_bytes!("envivio-352x288.264.framed");
+    let mut pts = 0;
+    for _ in 0..10 {
+        let mut offset: usize = 0;
+        while offset + 4 < example_bytes.len() {
+            let size_bytes = &example_bytes[offset..offset + 4];
+            let frame_size = u32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
+            offset += 4;
+            let frame_bytes = &example_bytes[offset..offset + frame_size];
+            offset += frame_size;
+            writer.write_bytes(frame_bytes, pts, 40000, is_key_frame(frame_bytes), 0)?;
+            pts += 40000;
+        }
+    }
+    writer.finish()?;
+
+    println!("Playlist written to {}/index.m3u8", output_path);
+
+    Ok(())
+}
@@ -1,10 +1,8 @@
 use ffav::easy::SimpleReader;
-use std::convert::TryInto;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
-use std::time::Instant;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let early_exit = Arc::new(AtomicBool::new(false));
@@ -18,9 +16,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut reader = SimpleReader::open("/tmp/envivio-352x288.264.mp4", None, None)?;
     for (frame, _info) in reader.frames() {
         println!("frame={:#?}", frame);
-        let bytes =
-            unsafe { std::slice::from_raw_parts(frame.data, frame.size.try_into().unwrap()) };
-        println!("bytes={:?}", &bytes[..16]);
+        let bytes = frame.data();
+        println!("bytes={:?}", &bytes[..16.min(bytes.len())]);
     }
 
     println!("streams()={:#?}", reader.streams());
@@ -39,7 +39,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let mut pts = 0;
         while offset + 4 < example_bytes.len() {
             let size_bytes = &example_bytes[offset..offset + 4];
-            let frame_size = i32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
+            let frame_size = u32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
             offset += 4;
             let frame_bytes = &example_bytes[offset..offset + frame_size];
             offset += frame_size;
@@ -0,0 +1,36 @@
+use ffav::easy::{OpenOptions, TeeWriter, VideoDesc, Writer};
+use std::convert::TryInto;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+    let mp4_writer = OpenOptions::new()
+        .media(v_desc)
+        .format_options("movflags=frag_keyframe")
+        .open("/tmp/envivio-352x288.264.tee.mp4")?;
+    let ts_writer = OpenOptions::new()
+        .media(v_desc)
+        .format("mpegts")
+        .open("/tmp/envivio-352x288.264.tee.ts")?;
+
+    // Keep writing the mp4 even if the mpegts failed, and vice versa.
+    let mut writer = TeeWriter::new(vec![mp4_writer, ts_writer]).best_effort(true);
+    let example_bytes = include_bytes!("envivio-352x288.264.framed");
+    let mut offset: usize = 0;
+    let mut pts = 0;
+    while offset + 4 < example_bytes.len() {
+        let size_bytes = &example_bytes[offset..offset + 4];
+        let frame_size = u32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
+        offset += 4;
+        let frame_bytes = &example_bytes[offset..offset + frame_size];
+        offset += frame_size;
+        writer.write_bytes(frame_bytes, pts, 40000, false, 0)?;
+        pts += 40000;
+    }
+    for (index, err) in writer.failures() {
+        println!("Writer #{} failed: {}", index, err);
+    }
+    println!("{} bytes written", writer.size());
+    Box::new(writer).finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use ffav::easy::{DataDesc, OpenOptions, VideoDesc};
+use std::convert::TryInto;
+
+/// Encode the size to the 28-bit syncsafe integer of ID3v2.4.
+fn syncsafe(size: usize) -> [u8; 4] {
+    [
+        ((size >> 21) & 0x7f) as u8,
+        ((size >> 14) & 0x7f) as u8,
+        ((size >> 7) & 0x7f) as u8,
+        (size & 0x7f) as u8,
+    ]
+}
+
+/// Build an ID3v2.4 tag with a single PRIV frame.
+fn id3_priv_tag(owner: &str, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::new();
+    frame.extend_from_slice(owner.as_bytes());
+    frame.push(0);
+    frame.extend_from_slice(data);
+
+    let mut tag = Vec::new();
+    tag.extend_from_slice(b"ID3\x04\x00\x00");
+    tag.extend_from_slice(&syncsafe(10 + frame.len()));
+    tag.extend_from_slice(b"PRIV");
+    tag.extend_from_slice(&syncsafe(frame.len()));
+    tag.extend_from_slice(&[0, 0]);
+    tag.extend_from_slice(&frame);
+    tag
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+    let d_desc = DataDesc::with_timed_id3(1000000);
+    let mut writer = OpenOptions::new()
+        .media(v_desc)
+        .media(d_desc)
+        .format("mpegts")
+        .open("/tmp/envivio-352x288.id3.ts")?;
+
+    // Emit the ad marker at 2 seconds.
+    let marker_pts = 2000000;
+    let marker = id3_priv_tag("com.example.ad-marker", b"break-1");
+    let example_bytes = include_bytes!("envivio-352x288.264.framed");
+    let mut offset: usize = 0;
+    let mut pts = 0;
+    while offset + 4 < example_bytes.len() {
+        let size_bytes = &example_bytes[offset..offset + 4];
+        let frame_size = u32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
+        offset += 4;
+        let frame_bytes = &example_bytes[offset..offset + frame_size];
+        offset += frame_size;
+        writer.write_bytes(frame_bytes, pts, 40000, false, 0)?;
+        if pts == marker_pts {
+            writer.write_bytes(&marker, pts, 0, true, 1)?;
+        }
+        pts += 40000;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use super::{owned::*, reader::*, AVResult};
+use futures_core::Stream;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::task::JoinHandle;
+
+type ReadTask = JoinHandle<(SimpleReader, Option<AVPacketOwned>)>;
+
+/// Asynchronous Reader for Demuxing Media Files.
+///
+/// The blocking reads of the `SimpleReader` are run on the `spawn_blocking`
+/// thread pool of tokio, the frames are yielded through a `Stream`.
+#[derive(Debug)]
+pub struct AsyncReader {
+    reader: Option<SimpleReader>,
+    task: Option<ReadTask>,
+}
+
+impl AsyncReader {
+    /// Wrap an opened simple reader.
+    pub fn new(reader: SimpleReader) -> Self {
+        Self {
+            reader: Some(reader),
+            task: None,
+        }
+    }
+
+    /// Open the input file without blocking the runtime, see `SimpleReader::open()`.
+    pub async fn open<P>(
+        path: P,
+        format_options: Option<&str>,
+        time_unit: Option<i32>,
+    ) -> AVResult<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let format_options = format_options.map(String::from);
+        let reader = tokio::task::spawn_blocking(move || {
+            SimpleReader::open(path, format_options.as_deref(), time_unit)
+                .map_err(|e| e.to_string())
+        })
+        .await??;
+        Ok(Self::new(reader))
+    }
+
+    /// Open the input url without blocking the runtime, see `SimpleReader::open_url()`.
+    pub async fn open_url(
+        url: &str,
+        format_options: Option<&str>,
+        time_unit: Option<i32>,
+    ) -> AVResult<Self> {
+        let url = url.to_owned();
+        let format_options = format_options.map(String::from);
+        let reader = tokio::task::spawn_blocking(move || {
+            SimpleReader::open_url(&url, format_options.as_deref(), time_unit)
+                .map_err(|e| e.to_string())
+        })
+        .await??;
+        Ok(Self::new(reader))
+    }
+
+    /// Return the next frame of a stream.
+    pub async fn read_frame(&mut self) -> Option<AVPacketOwned> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Returns the underly reader, `None` if a read is in progress.
+    pub fn get_ref(&self) -> Option<&SimpleReader> {
+        self.reader.as_ref()
+    }
+}
+
+impl Stream for AsyncReader {
+    type Item = AVPacketOwned;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.task.is_none() {
+            let mut reader = match self.reader.take() {
+                Some(reader) => reader,
+                None => return Poll::Ready(None),
+            };
+            self.task = Some(tokio::task::spawn_blocking(move || {
+                let frame = reader.read_frame();
+                (reader, frame)
+            }));
+        }
+        let task = self.task.as_mut().unwrap();
+        match Pin::new(task).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(result) => {
+                self.task = None;
+                match result {
+                    Ok((reader, frame)) => {
+                        self.reader = Some(reader);
+                        Poll::Ready(frame)
+                    }
+                    // The reader is lost if the read panicked.
+                    Err(_) => Poll::Ready(None),
+                }
+            }
+        }
+    }
+}
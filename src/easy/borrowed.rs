@@ -1,11 +1,82 @@
-﻿use crate::ffi::{AVCodecID::*, *};
-use std::error::Error;
-use std::ffi::CString;
+﻿use super::AVResult;
+use crate::ffi::*;
+use std::convert::TryInto;
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
-use std::path::Path;
 
+/// Borrowed packet for the bytes without copying.
+///
+/// The `data` of the packet points into the bytes, the lifetime ensures
+/// the packet can't outlive the bytes.
 #[repr(transparent)]
-struct AVBytesPacket(AVPacket);
+pub struct AVBytesPacket<'a> {
+    inner: AVPacket,
+    _marker: PhantomData<&'a [u8]>,
+}
 
+impl<'a> std::fmt::Debug for AVBytesPacket<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            write!(f, "{:#?}", self.inner)
+        } else {
+            write!(f, "{:?}", self.inner)
+        }
+    }
+}
 
-impl 
\ No newline at end of file
+impl<'a> Deref for AVBytesPacket<'a> {
+    type Target = AVPacket;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<'a> DerefMut for AVBytesPacket<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<'a> AVBytesPacket<'a> {
+    /// Create a new packet borrows the bytes.
+    /// # Arguments
+    /// * `bytes` - Stream byte data.
+    /// * `pts` - Presentation timestamp of the packet.
+    /// * `dts` - Decompression timestamp of the packet.
+    /// * `duration` - Duration of the packet.
+    /// * `flags` - Flags of the packet, like: `AV_PKT_FLAG_KEY`.
+    /// * `stream_index` - Index of the stream.
+    pub fn new(
+        bytes: &'a [u8],
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        flags: i32,
+        stream_index: usize,
+    ) -> AVResult<Self> {
+        let inner = AVPacket {
+            pts,
+            dts,
+            data: bytes.as_ptr() as *mut u8,
+            size: bytes.len().try_into()?,
+            stream_index: stream_index.try_into()?,
+            flags,
+            duration,
+            pos: -1,
+            ..Default::default()
+        };
+        Ok(Self {
+            inner,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn as_ptr(&self) -> *const AVPacket {
+        &self.inner as *const AVPacket
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut AVPacket {
+        &mut self.inner
+    }
+}
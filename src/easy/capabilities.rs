@@ -0,0 +1,184 @@
+use crate::ffi::*;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+/// Returns the names of all muxers supported by the linked FFmpeg.
+pub fn available_muxers() -> Vec<String> {
+    let mut names = vec![];
+    let mut opaque = std::ptr::null_mut();
+    unsafe {
+        loop {
+            let ofmt = av_muxer_iterate(&mut opaque);
+            if ofmt.is_null() {
+                break;
+            }
+            names.push(to_string((*ofmt).name));
+        }
+    }
+    names
+}
+
+/// Returns the names of all demuxers supported by the linked FFmpeg.
+pub fn available_demuxers() -> Vec<String> {
+    let mut names = vec![];
+    let mut opaque = std::ptr::null_mut();
+    unsafe {
+        loop {
+            let ifmt = av_demuxer_iterate(&mut opaque);
+            if ifmt.is_null() {
+                break;
+            }
+            names.push(to_string((*ifmt).name));
+        }
+    }
+    names
+}
+
+/// Returns `true` if the muxer with the given name is supported.
+pub fn has_muxer(name: &str) -> bool {
+    available_muxers().iter().any(|x| x == name)
+}
+
+/// Returns `true` if the demuxer with the given name is supported.
+///
+/// Some demuxers have a comma separated list of names, like: `mov,mp4,m4a,3gp,3g2,mj2`,
+/// each of them is matched.
+pub fn has_demuxer(name: &str) -> bool {
+    available_demuxers()
+        .iter()
+        .any(|x| x.split(',').any(|x| x == name))
+}
+
+/// Returns `true` if an encoder for the codec is supported.
+pub fn has_encoder(id: AVCodecID) -> bool {
+    unsafe { !avcodec_find_encoder(id).is_null() }
+}
+
+/// Returns the codec encoded by the encoder of the name, like: `h264_nvenc`,
+/// `None` if the encoder is not compiled into the linked FFmpeg.
+pub fn encoder_codec_id(name: &str) -> Option<AVCodecID> {
+    let name = std::ffi::CString::new(name).ok()?;
+    unsafe {
+        let codec = avcodec_find_encoder_by_name(name.as_ptr());
+        if codec.is_null() {
+            None
+        } else {
+            Some((*codec).id)
+        }
+    }
+}
+
+/// Returns `true` if the codec has inter-frame GOP, the splitting should be aligned to key frames.
+///
+/// The codec descriptor is consulted, the video codecs without `AV_CODEC_PROP_INTRA_ONLY`
+/// property have GOP, returns `false` for the other media types and unknown codecs.
+pub fn codec_has_gop(id: AVCodecID) -> bool {
+    unsafe {
+        let desc = avcodec_descriptor_get(id);
+        if desc.is_null() || (*desc).type_ != AVMediaType::AVMEDIA_TYPE_VIDEO {
+            false
+        } else {
+            ((*desc).props as u32 & AV_CODEC_PROP_INTRA_ONLY) == 0
+        }
+    }
+}
+
+/// Returns the pixel formats supported by the encoder of the codec.
+///
+/// Empty if the encoder accepts any pixel format or not found.
+pub fn supported_pix_fmts(id: AVCodecID) -> Vec<AVPixelFormat> {
+    unsafe {
+        let codec = avcodec_find_encoder(id);
+        if codec.is_null() {
+            vec![]
+        } else {
+            to_vec((*codec).pix_fmts, AVPixelFormat::AV_PIX_FMT_NONE)
+        }
+    }
+}
+
+/// Returns the sample formats supported by the encoder of the codec.
+///
+/// Empty if the encoder accepts any sample format or not found.
+pub fn supported_sample_fmts(id: AVCodecID) -> Vec<AVSampleFormat> {
+    unsafe {
+        let codec = avcodec_find_encoder(id);
+        if codec.is_null() {
+            vec![]
+        } else {
+            to_vec((*codec).sample_fmts, AVSampleFormat::AV_SAMPLE_FMT_NONE)
+        }
+    }
+}
+
+/// Returns the sample rates supported by the encoder of the codec.
+///
+/// Empty if the encoder accepts any sample rate or not found.
+pub fn supported_sample_rates(id: AVCodecID) -> Vec<i32> {
+    unsafe {
+        let codec = avcodec_find_encoder(id);
+        if codec.is_null() {
+            vec![]
+        } else {
+            to_vec((*codec).supported_samplerates, 0)
+        }
+    }
+}
+
+/// Copy the list terminated by the `end` value, empty if the `ptr` is null.
+unsafe fn to_vec<T: Copy + PartialEq>(ptr: *const T, end: T) -> Vec<T> {
+    let mut items = vec![];
+    if !ptr.is_null() {
+        let mut p = ptr;
+        while *p != end {
+            items.push(*p);
+            p = p.add(1);
+        }
+    }
+    items
+}
+
+unsafe fn to_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_has_gop() {
+        assert!(codec_has_gop(AVCodecID::AV_CODEC_ID_H264));
+        assert!(codec_has_gop(AVCodecID::AV_CODEC_ID_HEVC));
+        assert!(codec_has_gop(AVCodecID::AV_CODEC_ID_VP9));
+        assert!(codec_has_gop(AVCodecID::AV_CODEC_ID_AV1));
+        assert!(!codec_has_gop(AVCodecID::AV_CODEC_ID_MJPEG));
+        assert!(!codec_has_gop(AVCodecID::AV_CODEC_ID_AAC));
+        assert!(!codec_has_gop(AVCodecID::AV_CODEC_ID_MOV_TEXT));
+        assert!(!codec_has_gop(AVCodecID::AV_CODEC_ID_NONE));
+    }
+
+    #[test]
+    fn test_encoder_codec_id() {
+        assert_eq!(encoder_codec_id("png"), Some(AVCodecID::AV_CODEC_ID_PNG));
+        assert_eq!(encoder_codec_id("no-such-encoder"), None);
+    }
+
+    #[test]
+    fn test_supported_formats() {
+        assert!(supported_pix_fmts(AVCodecID::AV_CODEC_ID_PNG)
+            .contains(&AVPixelFormat::AV_PIX_FMT_RGB24));
+        assert!(supported_pix_fmts(AVCodecID::AV_CODEC_ID_NONE).is_empty());
+        assert_eq!(
+            supported_sample_fmts(AVCodecID::AV_CODEC_ID_AAC),
+            vec![AVSampleFormat::AV_SAMPLE_FMT_FLTP]
+        );
+        assert!(supported_sample_rates(AVCodecID::AV_CODEC_ID_AAC).contains(&48000));
+        // The PCM encoders accept any sample rate.
+        assert!(supported_sample_rates(AVCodecID::AV_CODEC_ID_PCM_S16LE).is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+use super::{owned::*, rescale::*, side_data::*, AVResult};
+use crate::ffi::{AVCodecID::*, AVMediaType::*, AVPixelFormat::*, *};
+use std::convert::TryInto;
+use std::path::Path;
+use std::time::Duration;
+
+/// Extract the frame at the position of the video as a PNG image.
+///
+/// See `extract_frame_as()` for details.
+pub fn extract_frame<P>(path: P, at: Duration) -> AVResult<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    extract_frame_as(path, at, AV_CODEC_ID_PNG)
+}
+
+/// Extract the frame at the position of the video as an image.
+///
+/// The input seeks to the key frame before `at`, then decodes forward to the
+/// first frame at or after `at`, the last frame is used if `at` is beyond the
+/// end. The frame is stretched per the sample aspect ratio, rotated per the
+/// display matrix, then converted to the pixel format of the image and encoded,
+/// like: the thumbnails of the portrait videos of the phones.
+/// # Arguments
+/// * `path` - Path of the input file, the first video stream is used.
+/// * `at` - Position of the frame, relative to the start of the input.
+/// * `codec_id` - Codec of the image, `AV_CODEC_ID_PNG` or `AV_CODEC_ID_MJPEG` (JPEG).
+pub fn extract_frame_as<P>(path: P, at: Duration, codec_id: AVCodecID) -> AVResult<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let pix_fmt = match codec_id {
+        AV_CODEC_ID_PNG => AV_PIX_FMT_RGB24,
+        AV_CODEC_ID_MJPEG => AV_PIX_FMT_YUVJ420P,
+        _ => return Err(format!("Unsupported image codec: {:?}", codec_id).into()),
+    };
+    let (frame, display) = decode_frame_at(path.as_ref(), at)?;
+    encode_image(&frame, display, codec_id, pix_fmt)
+}
+
+/// Extract the frame at the position of the video as an `image::RgbImage`.
+///
+/// The frame is stretched and rotated for displaying like `extract_frame_as()`,
+/// without encoding.
+#[cfg(feature = "image")]
+pub fn extract_image<P>(path: P, at: Duration) -> AVResult<image::RgbImage>
+where
+    P: AsRef<Path>,
+{
+    let (frame, display) = decode_frame_at(path.as_ref(), at)?;
+    display_rgb(&frame, display)?.to_image()
+}
+
+/// The transform to display the frames of a stream.
+#[derive(Copy, Clone, Debug)]
+struct Display {
+    /// Sample aspect ratio of the stream, `0/1` if unknown.
+    sample_aspect_ratio: AVRational,
+    /// Rotation angle in degrees (counterclockwise) of the display matrix.
+    rotation: f64,
+}
+
+/// Decode the first frame at or after the position of the first video stream.
+fn decode_frame_at(path: &Path, at: Duration) -> AVResult<(AVFrameOwned, Display)> {
+    let mut ctx = AVFormatContextOwned::with_input(path, None)?;
+    let stream = ctx
+        .streams()
+        .iter()
+        .find(|x| match x.codecpar() {
+            Some(par) => {
+                par.codec_type == AVMEDIA_TYPE_VIDEO
+                    && (x.disposition & AV_DISPOSITION_ATTACHED_PIC) == 0
+            }
+            None => false,
+        })
+        .ok_or("No video stream found")?;
+    let stream_index = stream.index;
+    let time_base = stream.time_base;
+    let display = Display {
+        sample_aspect_ratio: stream.sample_aspect_ratio,
+        rotation: stream.rotation_degrees().unwrap_or(0.0),
+    };
+    let par = stream.codecpar().unwrap();
+    let mut decoder = AVCodecContextOwned::with_decoder(par.codec_id)?;
+    decoder.set_parameters(par)?;
+    decoder.open(None)?;
+
+    let mut timestamp: i64 = at.as_micros().try_into()?;
+    if ctx.start_time != AV_NOPTS_VALUE {
+        timestamp += ctx.start_time;
+    }
+    ctx.seek(timestamp)?;
+    let target = rescale_ts(timestamp, AV_TIME_BASE_Q, time_base);
+    let mut last_frame: Option<AVFrameOwned> = None;
+    loop {
+        let packet = match ctx.try_read_frame() {
+            Ok(packet) if packet.stream_index != stream_index => continue,
+            Ok(packet) => Some(packet),
+            // Drain the decoder at the end of the input.
+            Err(AVReadError::Eof) => None,
+            Err(err) => return Err(err.into()),
+        };
+        // The corrupted packets are skipped.
+        let _ = decoder.send_packet(packet.as_deref());
+        loop {
+            match decoder.receive_frame() {
+                Ok(frame) => {
+                    let ts = frame.best_effort_timestamp;
+                    if ts == AV_NOPTS_VALUE || ts >= target {
+                        return Ok((frame, display));
+                    }
+                    last_frame = Some(frame);
+                }
+                Err(AVCodecError::Again) => break,
+                Err(AVCodecError::Eof) => {
+                    return last_frame
+                        .map(|x| (x, display))
+                        .ok_or_else(|| "No frame decoded".into());
+                }
+                Err(AVCodecError::Reason(reason)) => return Err(reason.into()),
+            }
+        }
+    }
+}
+
+/// Transform the frame for displaying, convert to the pixel format and encode
+/// to an image.
+fn encode_image(
+    frame: &AVFrame,
+    display: Display,
+    codec_id: AVCodecID,
+    pix_fmt: AVPixelFormat,
+) -> AVResult<Vec<u8>> {
+    let mut image = display_rgb(frame, display)?;
+    if pix_fmt != AV_PIX_FMT_RGB24 {
+        let mut scaler = AVSwsContextOwned::new(
+            image.width,
+            image.height,
+            AV_PIX_FMT_RGB24,
+            image.width,
+            image.height,
+            pix_fmt,
+        )?;
+        image = scaler.scale(&image)?;
+    }
+    image.pts = 0;
+    let mut encoder = AVCodecContextOwned::with_encoder(codec_id)?;
+    encoder.width = image.width;
+    encoder.height = image.height;
+    encoder.pix_fmt = pix_fmt;
+    encoder.time_base = AVRational::new(1, 25);
+    encoder.open(None)?;
+    encoder.send_frame(Some(&image))?;
+    encoder.send_frame(None)?;
+    let packet = encoder.receive_packet()?;
+    Ok(packet.data().to_vec())
+}
+
+/// Stretch the frame per the sample aspect ratio and rotate per the display
+/// matrix, to an RGB24 frame.
+fn display_rgb(frame: &AVFrame, display: Display) -> AVResult<AVFrameOwned> {
+    // The sample aspect ratio of the container overrides the bitstream's.
+    let sample_aspect_ratio = if display.sample_aspect_ratio.num > 0 {
+        display.sample_aspect_ratio
+    } else {
+        frame.sample_aspect_ratio
+    };
+    let (width, height) = display_size(frame.width, frame.height, sample_aspect_ratio);
+    let mut scaler = AVSwsContextOwned::new(
+        frame.width,
+        frame.height,
+        pix_fmt_from_raw(frame.format)?,
+        width,
+        height,
+        AV_PIX_FMT_RGB24,
+    )?;
+    let mut image = scaler.scale(frame)?;
+    let quarter_turns = ((display.rotation / 90.0).round() as i64).rem_euclid(4);
+    if quarter_turns != 0 {
+        image = rotate_rgb(&image, quarter_turns)?;
+    }
+    Ok(image)
+}
+
+/// Returns the size of the frame stretched horizontally per the sample aspect ratio.
+fn display_size(width: i32, height: i32, sample_aspect_ratio: AVRational) -> (i32, i32) {
+    let AVRational { num, den } = sample_aspect_ratio;
+    if num > 0 && den > 0 && num != den {
+        let width = (i64::from(width) * i64::from(num) + i64::from(den) / 2) / i64::from(den);
+        (width as i32, height)
+    } else {
+        (width, height)
+    }
+}
+
+/// Rotate the RGB24 image counterclockwise by the quarter turns.
+fn rotate_rgb(image: &AVFrameOwned, quarter_turns: i64) -> AVResult<AVFrameOwned> {
+    let (w, h) = (image.width as usize, image.height as usize);
+    let (dw, dh) = if quarter_turns % 2 == 1 {
+        (h, w)
+    } else {
+        (w, h)
+    };
+    let mut rotated = AVFrameOwned::with_video(dw as i32, dh as i32, AV_PIX_FMT_RGB24)?;
+    let src = image.plane(0);
+    let src_stride = image.linesize[0] as usize;
+    let dst_stride = rotated.linesize[0] as usize;
+    let dst = unsafe { std::slice::from_raw_parts_mut(rotated.data[0], dst_stride * dh) };
+    for y in 0..dh {
+        for x in 0..dw {
+            // The source of the pixel, rows are top-down.
+            let (sx, sy) = match quarter_turns {
+                1 => (w - 1 - y, x),
+                2 => (w - 1 - x, h - 1 - y),
+                _ => (y, h - 1 - x),
+            };
+            let s = sy * src_stride + sx * 3;
+            let d = y * dst_stride + x * 3;
+            dst[d..d + 3].copy_from_slice(&src[s..s + 3]);
+        }
+    }
+    rotated.pts = image.pts;
+    Ok(rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy::fixture::example_frames;
+    use crate::easy::{SimpleWriter, VideoDesc, Writer};
+
+    #[test]
+    fn test_extract_frame() {
+        let path = "/tmp/ffav-extract.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+
+        let png = extract_frame(path, Duration::from_secs(1)).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let jpeg = extract_frame_as(path, Duration::from_secs(1), AV_CODEC_ID_MJPEG).unwrap();
+        assert_eq!(&jpeg[..2], &[0xff, 0xd8]);
+        assert!(extract_frame_as(path, Duration::from_secs(1), AV_CODEC_ID_H264).is_err());
+    }
+
+    #[test]
+    fn test_extract_frame_display() {
+        let path = "/tmp/ffav-extract-display.mp4";
+        let mut v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        v_desc.rotation = Some(90.0);
+        v_desc.sample_aspect_ratio = AVRational::new(2, 1);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+
+        // The width and height in the IHDR chunk, stretched then rotated.
+        let png = extract_frame(path, Duration::from_secs(1)).unwrap();
+        assert_eq!(&png[16..20], &288u32.to_be_bytes());
+        assert_eq!(&png[20..24], &704u32.to_be_bytes());
+        #[cfg(feature = "image")]
+        {
+            let image = extract_image(path, Duration::from_secs(1)).unwrap();
+            assert_eq!(image.dimensions(), (288, 704));
+        }
+    }
+
+    #[test]
+    fn test_rotate_rgb() {
+        let image = AVFrameOwned::with_video(2, 1, AV_PIX_FMT_RGB24).unwrap();
+        unsafe {
+            std::slice::from_raw_parts_mut(image.data[0], 6).copy_from_slice(&[1, 1, 1, 2, 2, 2]);
+        }
+        // The right pixel goes to the top by counterclockwise.
+        let rotated = rotate_rgb(&image, 1).unwrap();
+        assert_eq!((rotated.width, rotated.height), (1, 2));
+        assert_eq!(rotated.plane(0)[0], 2);
+        let rotated = rotate_rgb(&image, 3).unwrap();
+        assert_eq!(rotated.plane(0)[0], 1);
+        let rotated = rotate_rgb(&image, 2).unwrap();
+        assert_eq!(&rotated.plane(0)[..6], &[2, 2, 2, 1, 1, 1]);
+    }
+}
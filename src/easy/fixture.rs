@@ -0,0 +1,25 @@
+use std::convert::TryInto;
+
+const EXAMPLE_BYTES: &[u8] = include_bytes!("../../examples/envivio-352x288.264.framed");
+
+/// Returns the frames of the example, strip the length prefixes.
+pub(crate) fn example_frames() -> impl Iterator<Item = &'static [u8]> {
+    let mut offset: usize = 0;
+    std::iter::from_fn(move || {
+        if offset + 4 >= EXAMPLE_BYTES.len() {
+            return None;
+        }
+        let size_bytes = &EXAMPLE_BYTES[offset..offset + 4];
+        let frame_size = u32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
+        offset += 4;
+        let frame_bytes = &EXAMPLE_BYTES[offset..offset + frame_size];
+        offset += frame_size;
+        Some(frame_bytes)
+    })
+}
+
+/// Write the example as the raw Annex B stream.
+pub(crate) fn write_raw_es(path: &str) {
+    let raw_bytes: Vec<u8> = example_frames().flatten().copied().collect();
+    std::fs::write(path, &raw_bytes).unwrap();
+}
@@ -0,0 +1,231 @@
+use crate::ffi::*;
+use std::fs::File;
+use std::io::{Cursor, Error, ErrorKind, Result, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A file opened for appending to the existing data.
+///
+/// The positions are relative to the end of the existing data, so the muxer
+/// writes as it was an empty file. Unlike `O_APPEND`, seeking backward within
+/// the appended data is allowed, the existing data is never touched.
+#[derive(Debug)]
+pub struct AppendFile {
+    file: File,
+    base: u64,
+}
+
+impl AppendFile {
+    /// Open or create the file for appending.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)?;
+        let base = file.seek(SeekFrom::End(0))?;
+        Ok(Self { file, base })
+    }
+
+    /// Returns the size of the existing data when opened.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+}
+
+impl Write for AppendFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for AppendFile {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.base + offset),
+            pos => pos,
+        };
+        let new_pos = self.file.seek(pos)?;
+        if new_pos < self.base {
+            self.file.seek(SeekFrom::Start(self.base))?;
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Seek before the appended data",
+            ));
+        }
+        Ok(new_pos - self.base)
+    }
+}
+
+/// A growable in-memory buffer, the clones share the same data.
+///
+/// The muxer writes through one clone, the data is taken from another.
+#[derive(Clone, Debug, Default)]
+pub struct SharedBuffer {
+    inner: Arc<Mutex<Cursor<Vec<u8>>>>,
+}
+
+impl SharedBuffer {
+    /// Create an empty buffer.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Take the data out and leave the buffer empty.
+    pub fn take(&self) -> Vec<u8> {
+        let mut cursor = self.lock();
+        cursor.set_position(0);
+        std::mem::take(cursor.get_mut())
+    }
+
+    /// Returns the length of the data.
+    pub fn len(&self) -> usize {
+        self.lock().get_ref().len()
+    }
+
+    /// Returns `true` if no data written.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cursor<Vec<u8>>> {
+        // The cursor is always consistent, even if a writer panicked.
+        match self.inner.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SharedBuffer {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.lock().seek(pos)
+    }
+}
+
+/// The CRC-32 (IEEE 802.3, as zlib) of the bytes written by `ChecksumWriter`,
+/// the clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct Checksum {
+    inner: Arc<Mutex<ChecksumState>>,
+}
+
+#[derive(Debug, Default)]
+struct ChecksumState {
+    crc: u32,
+    len: u64,
+    rewritten: bool,
+}
+
+impl Checksum {
+    /// Create an empty checksum.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the CRC-32 of the bytes written, `None` if the bytes written
+    /// before were rewritten by seeking back, like: the mp4 muxer patches
+    /// the size of the `mdat` box in the trailer.
+    pub fn value(&self) -> Option<u32> {
+        let state = self.lock();
+        if state.rewritten {
+            None
+        } else {
+            Some(state.crc)
+        }
+    }
+
+    /// Returns the number of the bytes written.
+    pub fn len(&self) -> u64 {
+        self.lock().len
+    }
+
+    /// Returns `true` if no bytes written.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Update with the bytes written at the position.
+    fn update(&self, pos: u64, buf: &[u8]) {
+        let mut state = self.lock();
+        if pos == state.len && !state.rewritten {
+            state.crc = crc32(state.crc, buf);
+        } else {
+            state.rewritten = true;
+        }
+        state.len = state.len.max(pos + buf.len() as u64);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ChecksumState> {
+        // The state is always consistent, even if a writer panicked.
+        match self.inner.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Continue the CRC-32 (IEEE 802.3, as zlib) of the bytes, starts with `0`.
+pub fn crc32(crc: u32, buf: &[u8]) -> u32 {
+    unsafe {
+        let table = av_crc_get_table(AVCRCId::AV_CRC_32_IEEE_LE);
+        !av_crc(table, !crc, buf.as_ptr(), buf.len() as _)
+    }
+}
+
+/// A writer computes the checksum of the bytes written to the inner writer.
+#[derive(Debug)]
+pub struct ChecksumWriter<W> {
+    inner: W,
+    checksum: Checksum,
+    pos: u64,
+}
+
+impl<W> ChecksumWriter<W> {
+    /// Create a writer updates the `checksum` with the bytes written.
+    pub fn new(inner: W, checksum: Checksum) -> Self {
+        Self {
+            inner,
+            checksum,
+            pos: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.checksum.update(self.pos, &buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for ChecksumWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
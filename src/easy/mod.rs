@@ -3,11 +3,60 @@
 /// Generic Result.
 pub type AVResult<T> = Result<T, Box<dyn Error>>;
 
+/// Log a warning by the `log` crate if the `log` feature enabled, otherwise
+/// discarded, for the errors can not be returned, like: in the `Drop`.
+macro_rules! log_warn {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::warn!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    };
+}
+
+/// Log a trace by the `log` crate if the `log` feature enabled, otherwise
+/// discarded, for the high-frequency events, like: the packets dropped.
+macro_rules! log_trace {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::trace!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    };
+}
+
+#[cfg(feature = "tokio")]
+pub mod async_reader;
+#[cfg(feature = "tokio")]
+pub use async_reader::*;
+
+pub mod borrowed;
+pub use borrowed::*;
+
+pub mod capabilities;
+pub use capabilities::*;
+
+pub mod extract;
+pub use extract::*;
+
+pub mod io;
+pub use io::*;
+
 pub mod owned;
 pub use owned::*;
 
 pub mod reader;
 pub use reader::*;
 
+pub mod rescale;
+pub use rescale::*;
+
+pub mod side_data;
+pub use side_data::*;
+
 pub mod writer;
 pub use writer::*;
+
+/// The example stream shared by the tests and the benchmarks.
+#[cfg(test)]
+pub(crate) mod fixture;
@@ -1,61 +1,187 @@
-use super::{owned::*, AVResult};
+use super::{owned::*, rescale::*, AVResult};
 use crate::ffi::*;
-use std::fmt::Debug;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::fmt::{Debug, Write};
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Copy, Clone, Default, Debug)]
 pub struct FrameInfo {
     pub codec_id: AVCodecID,
     pub codec_type: AVMediaType,
+    /// Time base of the timestamps, the `time_unit` of the reader if specified,
+    /// otherwise the native time base of the stream.
+    pub time_base: AVRational,
+    /// Presentation timestamp of the frame, `None` if not available or negative.
+    pub pts: Option<Duration>,
+    /// Decompression timestamp of the frame, `None` if not available or negative.
+    pub dts: Option<Duration>,
+    /// Duration of the frame.
+    pub duration: Duration,
+}
+
+impl FrameInfo {
+    /// Returns a copy with the timing of the packet filled.
+    pub fn with_timing(mut self, packet: &AVPacket) -> Self {
+        self.pts = pts_to_duration(packet.pts, self.time_base);
+        self.dts = pts_to_duration(packet.dts, self.time_base);
+        self.duration = pts_to_duration(packet.duration, self.time_base).unwrap_or_default();
+        self
+    }
 }
 
 pub struct FrameIter<'a> {
     reader: &'a mut SimpleReader,
     frame_infos: Vec<FrameInfo>,
+    last_error: Option<AVReadError>,
 }
 
 impl<'a> Iterator for FrameIter<'a> {
     type Item = (AVPacketOwned, FrameInfo);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(frame) = self.reader.read_frame() {
-            let stream_index = frame.stream_index as usize;
-            Some((frame, self.frame_infos[stream_index]))
-        } else {
-            None
+        match self.reader.try_read_frame() {
+            Ok(frame) => {
+                let stream_index = frame.stream_index as usize;
+                let info = self.frame_infos[stream_index].with_timing(&frame);
+                Some((frame, info))
+            }
+            Err(AVReadError::Eof) => None,
+            Err(err) => {
+                self.last_error = Some(err);
+                None
+            }
         }
     }
 }
 
 impl<'a> FrameIter<'a> {
     pub fn new(reader: &'a mut SimpleReader) -> Self {
-        let frame_infos: Vec<FrameInfo> = reader
-            .streams()
-            .iter()
-            .map(|stream| {
-                if let Some(codecpar) = stream.codecpar() {
-                    FrameInfo {
-                        codec_id: codecpar.codec_id,
-                        codec_type: codecpar.codec_type,
-                    }
-                } else {
-                    FrameInfo::default()
-                }
-            })
-            .collect();
+        let frame_infos = reader.frame_infos();
         Self {
             reader,
             frame_infos,
+            last_error: None,
         }
     }
+
+    /// Returns the error stopped the iteration, `None` if ended by the end of
+    /// the input, like: to tell a truncated download from a complete file.
+    pub fn last_error(&self) -> Option<&AVReadError> {
+        self.last_error.as_ref()
+    }
 }
 
+/// Iterator over the frames within a time range.
+pub struct RangeFrameIter<'a> {
+    inner: FrameIter<'a>,
+    start: Duration,
+    end: Duration,
+    keyframe_aligned: bool,
+}
+
+impl<'a> RangeFrameIter<'a> {
+    /// Returns the error stopped the iteration, see `FrameIter::last_error()`.
+    pub fn last_error(&self) -> Option<&AVReadError> {
+        self.inner.last_error()
+    }
+}
+
+impl<'a> Iterator for RangeFrameIter<'a> {
+    type Item = (AVPacketOwned, FrameInfo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (frame, info) = self.inner.next()?;
+            match info.pts {
+                Some(pts) if pts > self.end => return None,
+                Some(pts) if pts < self.start && !self.keyframe_aligned => continue,
+                _ => return Some((frame, info)),
+            }
+        }
+    }
+}
+
+/// The reason of the reader stopped returning frames.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TerminationReason {
+    /// End of the input reached, all frames are read.
+    Eof,
+    /// Aborted by the interrupt flag.
+    Interrupted,
+    /// No data received within the read timeout.
+    Timeout,
+    /// The I/O or demuxing error.
+    Error(String),
+}
+
+impl From<&AVReadError> for TerminationReason {
+    fn from(err: &AVReadError) -> Self {
+        match err {
+            AVReadError::Eof => TerminationReason::Eof,
+            AVReadError::Interrupted => TerminationReason::Interrupted,
+            AVReadError::Timeout => TerminationReason::Timeout,
+            AVReadError::Reason(reason) => TerminationReason::Error(reason.clone()),
+        }
+    }
+}
+
+/// Statistics of reading a stream.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StreamStats {
+    /// Number of the packets read.
+    pub packets: u64,
+    /// Number of the payload bytes read.
+    pub bytes: u64,
+    /// Number of the pts jumped backward or forward beyond the threshold.
+    pub discontinuities: u64,
+}
+
+/// Statistics of the demuxing, by the index of the streams.
+#[derive(Clone, Debug, Default)]
+pub struct ReaderStats {
+    pub streams: Vec<StreamStats>,
+}
+
+impl ReaderStats {
+    /// Returns the number of the packets read of all streams.
+    pub fn packets(&self) -> u64 {
+        self.streams.iter().map(|x| x.packets).sum()
+    }
+
+    /// Returns the number of the payload bytes read of all streams.
+    pub fn bytes(&self) -> u64 {
+        self.streams.iter().map(|x| x.bytes).sum()
+    }
+}
+
+/// The default threshold of the pts jumps counted as discontinuities.
+pub const DEFAULT_DISCONTINUITY_THRESHOLD: Duration = Duration::from_secs(10);
+
 /// Simple Reader for Demuxing Media Files.
 #[derive(Debug)]
 pub struct SimpleReader {
     ctx: AVFormatContextOwned,
     bsfs: Vec<AVBSFContextOwned>,
     time_base: Option<AVRational>,
+    draining: bool,
+    /// The packet not accepted by the full filter, resent after its output.
+    pending_packet: Option<AVPacketOwned>,
+    stats: ReaderStats,
+    /// The last pts of each stream in `AV_TIME_BASE`.
+    last_pts: Vec<i64>,
+    discontinuity_threshold: Duration,
+    /// Subtract the start time from the timestamps.
+    normalize_timestamps: bool,
+    /// The start time subtracted from the timestamps in `AV_TIME_BASE`.
+    start_offset: Option<i64>,
+    /// The reason of the last read failed.
+    termination_reason: Option<TerminationReason>,
 }
 
 impl SimpleReader {
@@ -65,17 +191,105 @@ impl SimpleReader {
     /// * `format_options` - The options for demuxing format，like: movfragement.
     /// * `time_unit` - Convert the pts, dts or duration to specified time unit,
     //                  For example: convert to `us` unit: `time_unit=1000000`.
-    /// # Panics
     ///
+    /// See `ReaderOptions` for more options.
     pub fn open<P>(path: P, format_options: Option<&str>, time_unit: Option<i32>) -> AVResult<Self>
     where
         P: AsRef<Path> + Sized,
     {
-        let ctx = AVFormatContextOwned::with_input(path, format_options)?;
+        ReaderOptions::with_args(format_options, time_unit).open(path)
+    }
+
+    /// Create a new simple reader from an url.
+    ///
+    /// Only the network protocols in `DEFAULT_PROTOCOL_WHITELIST` are allowed,
+    /// unless `protocol_whitelist` specified in the `format_options`.
+    /// # Arguments
+    /// * `url` - Url of the input, like: `rtsp://192.168.1.10/stream1`.
+    /// * `format_options` - The options for demuxing format, in dictionary format like:
+    ///   `rtsp_transport=tcp:stimeout=5000000:buffer_size=1048576`.
+    /// * `time_unit` - Convert the pts, dts or duration to specified time unit,
+    //                  For example: convert to `us` unit: `time_unit=1000000`.
+    pub fn open_url(
+        url: &str,
+        format_options: Option<&str>,
+        time_unit: Option<i32>,
+    ) -> AVResult<Self> {
+        ReaderOptions::with_args(format_options, time_unit).open_url(url)
+    }
+
+    /// Create a new simple reader from an url, only the `protocols` are allowed.
+    ///
+    /// The nested protocols must be allowed too, like: `https` requires `tls`
+    /// and `tcp`.
+    pub fn open_url_with_protocols(
+        url: &str,
+        format_options: Option<&str>,
+        protocols: &[&str],
+        time_unit: Option<i32>,
+    ) -> AVResult<Self> {
+        let whitelist = protocols.join(",");
+        let format_options = protocol_options(format_options, Some(&whitelist), None)?;
+        let ctx = AVFormatContextOwned::with_input_url(url, Some(&format_options))?;
+        Self::with_context(ctx, time_unit, false)
+    }
+
+    /// Create a new simple reader from an url with an interrupt flag.
+    ///
+    /// The blocking operations, like: opening or reading from a network stream,
+    /// will be aborted as soon as the `interrupt` flag is set to `true`.
+    /// The protocols are restricted as `open_url()`.
+    pub fn open_url_with_interrupt(
+        url: &str,
+        format_options: Option<&str>,
+        interrupt: Arc<AtomicBool>,
+    ) -> AVResult<Self> {
+        let format_options = protocol_options(format_options, None, None)?;
+        let ctx =
+            AVFormatContextOwned::with_input_url_interrupt(url, Some(&format_options), interrupt)?;
+        Self::with_context(ctx, None, false)
+    }
+
+    /// Create a new simple reader to read the files one after another as a
+    /// continuous stream, like: the segments written by `SplitWriter`.
+    ///
+    /// The files are joined by the `concat` demuxer, the timestamps are continuous
+    /// across the boundaries and rescaled if the time bases of the files differ.
+    /// All files must have the same streams, in the same order.
+    /// # Arguments
+    /// * `paths` - Paths of the input files, in playback order.
+    /// * `time_unit` - Convert the pts, dts or duration to specified time unit,
+    //                  For example: convert to `us` unit: `time_unit=1000000`.
+    pub fn open_concat<P>(paths: &[P], time_unit: Option<i32>) -> AVResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let list = concat_list(paths)?;
+        static SEQ: AtomicUsize = AtomicUsize::new(0);
+        let list_path = std::env::temp_dir().join(format!(
+            "ffav-concat-{}-{}.ffconcat",
+            std::process::id(),
+            SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&list_path, list)?;
+        // The list is parsed entirely while opening, so it can be removed right after.
+        let ctx = AVFormatContextOwned::with_input(&list_path, Some("safe=0"));
+        let _ = std::fs::remove_file(&list_path);
+        Self::with_context(ctx?, time_unit, false)
+    }
+
+    fn with_context(
+        ctx: AVFormatContextOwned,
+        time_unit: Option<i32>,
+        skip_bsf: bool,
+    ) -> AVResult<Self> {
+        let nb_streams = ctx.streams().len();
         let mut bsfs: Vec<AVBSFContextOwned> = vec![];
         for stream in ctx.streams() {
             if let Some(codecpar) = stream.codecpar() {
+                // The raw elementary streams are Annex B already, tagged with 0.
                 let filter_name = match codecpar.codec_tag {
+                    _ if skip_bsf => "null",
                     AV_CODEC_TAG_AVC1 => "h264_mp4toannexb",
                     AV_CODEC_TAG_HEV1 | AV_CODEC_TAG_HVC1 => "hevc_mp4toannexb",
                     _ => "null",
@@ -89,6 +303,16 @@ impl SimpleReader {
             ctx,
             bsfs,
             time_base: time_unit.map(|x| AVRational::new(1, x)),
+            draining: false,
+            pending_packet: None,
+            stats: ReaderStats {
+                streams: vec![Default::default(); nb_streams],
+            },
+            last_pts: vec![AV_NOPTS_VALUE; nb_streams],
+            discontinuity_threshold: DEFAULT_DISCONTINUITY_THRESHOLD,
+            normalize_timestamps: false,
+            start_offset: None,
+            termination_reason: None,
         })
     }
 
@@ -102,18 +326,154 @@ impl SimpleReader {
         self.ctx.duration
     }
 
+    /// Print detailed information about the input format to stderr,
+    /// like: `Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'a.mp4':`.
+    pub fn dump(&self) {
+        unsafe {
+            let ptr = &*self.ctx as *const AVFormatContext as *mut AVFormatContext;
+            av_dump_format(ptr, 0, self.ctx.url, 0);
+        }
+    }
+
+    /// Returns the detailed information about the input format, the output is
+    /// similar to `dump()` but built from the stream descriptors.
+    pub fn dump_to_string(&self) -> String {
+        let mut s = String::new();
+        let url = unsafe {
+            if self.ctx.url.is_null() {
+                "".into()
+            } else {
+                CStr::from_ptr(self.ctx.url).to_string_lossy()
+            }
+        };
+        let _ = writeln!(s, "Input #0, {}, from '{}':", self.format_name(), url);
+        let _ = write!(s, "  Duration: {}", format_time(self.duration()));
+        let _ = write!(s, ", start: {}", format_start(self.start_time()));
+        let _ = writeln!(s, ", bitrate: {}", format_bit_rate(self.bit_rate()));
+        for (i, stream) in self.streams().iter().enumerate() {
+            let _ = write!(s, "    Stream #0:{}: ", i);
+            if let Some(par) = stream.codecpar() {
+                let codec_name = unsafe { CStr::from_ptr(avcodec_get_name(par.codec_id)) };
+                match par.codec_type {
+                    AVMediaType::AVMEDIA_TYPE_VIDEO => {
+                        let _ = write!(s, "Video: {}", codec_name.to_string_lossy());
+                        let _ = write!(s, ", {}x{}", par.width, par.height);
+                    }
+                    AVMediaType::AVMEDIA_TYPE_AUDIO => {
+                        let _ = write!(s, "Audio: {}", codec_name.to_string_lossy());
+                        let _ = write!(s, ", {} Hz, {} channels", par.sample_rate, par.channels);
+                    }
+                    AVMediaType::AVMEDIA_TYPE_SUBTITLE => {
+                        let _ = write!(s, "Subtitle: {}", codec_name.to_string_lossy());
+                    }
+                    AVMediaType::AVMEDIA_TYPE_DATA => {
+                        let _ = write!(s, "Data: {}", codec_name.to_string_lossy());
+                    }
+                    _ => {
+                        let _ = write!(s, "Unknown: {}", codec_name.to_string_lossy());
+                    }
+                }
+                if par.bit_rate > 0 {
+                    let _ = write!(s, ", {}", format_bit_rate(par.bit_rate));
+                }
+            }
+            let _ = writeln!(s);
+        }
+        s
+    }
+
+    /// Returns the short name of the detected input format, like: `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub fn format_name(&self) -> &str {
+        unsafe {
+            let iformat = self.ctx.iformat;
+            if iformat.is_null() || (*iformat).name.is_null() {
+                ""
+            } else {
+                CStr::from_ptr((*iformat).name).to_str().unwrap_or("")
+            }
+        }
+    }
+
+    /// Returns the descriptive name of the detected input format, like: `QuickTime / MOV`.
+    pub fn format_long_name(&self) -> &str {
+        unsafe {
+            let iformat = self.ctx.iformat;
+            if iformat.is_null() || (*iformat).long_name.is_null() {
+                ""
+            } else {
+                CStr::from_ptr((*iformat).long_name).to_str().unwrap_or("")
+            }
+        }
+    }
+
+    /// Returns the channel layout of the audio stream, like: `stereo` or `5.1(side)`.
+    ///
+    /// The number of channels is described, like: `3 channels`, if the layout
+    /// is unknown. Returns `None` if the stream is not audio.
+    ///
+    /// The `AVChannelLayout` API of FFmpeg 5.1 is not available in the bindings,
+    /// the legacy channel layout mask is described.
+    pub fn channel_layout(&self, stream_index: usize) -> Option<String> {
+        let par = self.stream(stream_index)?.codecpar()?;
+        if par.codec_type != AVMediaType::AVMEDIA_TYPE_AUDIO || par.channels <= 0 {
+            return None;
+        }
+        let mut buf = [0 as std::os::raw::c_char; 64];
+        unsafe {
+            av_get_channel_layout_string(
+                buf.as_mut_ptr(),
+                buf.len() as i32,
+                par.channels,
+                par.channel_layout,
+            );
+            Some(CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Returns the estimated number of frames of the stream.
+    ///
+    /// The `nb_frames` of the stream is used if present, otherwise calculated
+    /// from the duration and the average frame rate, so it's only an estimate
+    /// for the variable frame rate (VFR) content. Returns `None` if neither is
+    /// available, like: live streams.
+    pub fn estimated_frame_count(&self, stream_index: usize) -> Option<u64> {
+        let stream = self.stream(stream_index)?;
+        if stream.nb_frames > 0 {
+            return Some(stream.nb_frames as u64);
+        }
+        let frame_rate = stream.avg_frame_rate;
+        if frame_rate.num <= 0 || frame_rate.den <= 0 {
+            return None;
+        }
+        let frame_duration = frame_rate.inverse();
+        let frames = if stream.duration != AV_NOPTS_VALUE && stream.duration > 0 {
+            rescale_ts(stream.duration, stream.time_base, frame_duration)
+        } else if self.duration() != AV_NOPTS_VALUE && self.duration() > 0 {
+            rescale_ts(self.duration(), AV_TIME_BASE_Q, frame_duration)
+        } else {
+            return None;
+        };
+        Some(frames.max(0) as u64)
+    }
+
     /// Returns a list to describe the frame for each stream.
     pub fn frame_infos(&self) -> Vec<FrameInfo> {
         self.streams()
             .iter()
             .map(|stream| {
+                let time_base = self.time_base.unwrap_or(stream.time_base);
                 if let Some(codecpar) = stream.codecpar() {
                     FrameInfo {
                         codec_id: codecpar.codec_id,
                         codec_type: codecpar.codec_type,
+                        time_base,
+                        ..Default::default()
                     }
                 } else {
-                    FrameInfo::default()
+                    FrameInfo {
+                        time_base,
+                        ..Default::default()
+                    }
                 }
             })
             .collect()
@@ -124,61 +484,280 @@ impl SimpleReader {
         FrameIter::new(self)
     }
 
+    /// Returns an iterator over the frames within `[start, end]` of the timestamps.
+    ///
+    /// The reader seeks to the key frame at or before `start`, the frames before
+    /// `start` are skipped, so the leading frames may be undecodable without
+    /// their references. If `keyframe_aligned` is `true`, the `start` snaps back to
+    /// the preceding key frame and no frames are skipped, for lossless trimming.
+    pub fn frames_in_range(
+        &mut self,
+        start: Duration,
+        end: Duration,
+        keyframe_aligned: bool,
+    ) -> AVResult<RangeFrameIter<'_>> {
+        self.seek(start)?;
+        Ok(RangeFrameIter {
+            inner: FrameIter::new(self),
+            start,
+            end,
+            keyframe_aligned,
+        })
+    }
+
+    /// Seek to the key frame at or before the `position` of the timestamps.
+    pub fn seek(&mut self, position: Duration) -> AVResult<()> {
+        let mut timestamp: i64 = position.as_micros().try_into()?;
+        if let Some(start_offset) = self.start_offset {
+            timestamp += start_offset;
+        }
+        self.ctx.seek(timestamp)?;
+        self.reset_after_seek();
+        Ok(())
+    }
+
+    /// Seek to the byte position of the input, for recovering the partially
+    /// written files.
+    ///
+    /// The position is arbitrary, not aligned to a packet or a key frame,
+    /// the next `read_frame()` resynchronizes to the next valid packet.
+    /// Not supported by all the formats, like: mp4.
+    pub fn seek_byte(&mut self, byte_offset: i64) -> AVResult<()> {
+        self.ctx.seek_byte(byte_offset)?;
+        self.reset_after_seek();
+        Ok(())
+    }
+
+    /// Scan the input for the key frames of the first video stream, returns
+    /// the pts and the byte position of each, like a custom seek index.
+    ///
+    /// The input is read from the start, and rewound to the start after.
+    /// The key frames with unknown pts or position are skipped.
+    pub fn build_keyframe_index(&mut self) -> AVResult<Vec<(i64, i64)>> {
+        let stream_index = self
+            .streams()
+            .iter()
+            .position(|x| match x.codecpar() {
+                Some(par) => {
+                    par.codec_type == AVMediaType::AVMEDIA_TYPE_VIDEO
+                        && (x.disposition & AV_DISPOSITION_ATTACHED_PIC) == 0
+                }
+                None => false,
+            })
+            .ok_or("No video stream found")?;
+        self.seek(Duration::from_secs(0))?;
+        let mut index = vec![];
+        loop {
+            let packet = match self.try_read_frame() {
+                Ok(packet) => packet,
+                Err(AVReadError::Eof) => break,
+                Err(err) => return Err(err.into()),
+            };
+            if packet.stream_index as usize != stream_index || !packet.is_key_frame() {
+                continue;
+            }
+            if let (Some(pos), true) = (packet.pos(), packet.pts != AV_NOPTS_VALUE) {
+                index.push((packet.pts, pos));
+            }
+        }
+        self.seek(Duration::from_secs(0))?;
+        Ok(index)
+    }
+
+    /// Drop the states of the packets before seeking.
+    fn reset_after_seek(&mut self) {
+        for bsf in self.bsfs.iter_mut() {
+            bsf.flush();
+        }
+        self.draining = false;
+        self.pending_packet = None;
+        self.termination_reason = None;
+        // The jumps of seeking are not discontinuities.
+        for pts in self.last_pts.iter_mut() {
+            *pts = AV_NOPTS_VALUE;
+        }
+    }
+
+    /// Resume the network stream paused by `pause()`, like: RTSP `PLAY`.
+    ///
+    /// The error returned if the input does not support it, like: files.
+    pub fn play(&mut self) -> AVResult<()> {
+        self.ctx.play()
+    }
+
+    /// Pause the network stream, like: RTSP `PAUSE`.
+    ///
+    /// The error returned if the input does not support it, like: files.
+    ///
+    /// No packets received while paused, the `read_timeout` should be longer
+    /// than the pause, or the reading should be stopped until `play()`,
+    /// otherwise `AVReadError::Timeout` returned. The `interrupt` flag still
+    /// aborts the blocking calls.
+    pub fn pause(&mut self) -> AVResult<()> {
+        self.ctx.pause()
+    }
+
     /// Return the next frame of a stream.
     pub fn read_frame(&mut self) -> Option<AVPacketOwned> {
-        'outer: loop {
+        self.try_read_frame().ok()
+    }
+
+    /// Return the next frame of a stream or the reason of failure.
+    ///
+    /// The `AVReadError::Timeout` returned if the `read_timeout` specified
+    /// and no data received in time, the caller may reconnect to the source.
+    pub fn try_read_frame(&mut self) -> Result<AVPacketOwned, AVReadError> {
+        let result = self.read_next();
+        self.termination_reason = result.as_ref().err().map(TerminationReason::from);
+        result
+    }
+
+    /// Returns the reason of the reader stopped returning frames, `None` if
+    /// the last read succeeded or not read yet, cleared by seeking.
+    ///
+    /// Tells the end of the input from the interrupt, the timeout or the I/O
+    /// errors after `read_frame()` returned `None`, like: to reconnect.
+    pub fn termination_reason(&self) -> Option<TerminationReason> {
+        self.termination_reason.clone()
+    }
+
+    /// Read the next frame through the bitstream filters.
+    fn read_next(&mut self) -> Result<AVPacketOwned, AVReadError> {
+        loop {
             // Fetch frames from bitstream filter first.
             for bsf in self.bsfs.iter_mut() {
                 match bsf.receive_packet() {
                     Ok(packet) => {
-                        return Some(packet);
+                        return Ok(packet);
                     }
+                    // No more filtered packets from this filter for now.
                     Err(err) => match err {
                         AVBSFError::Again => {}
+                        AVBSFError::Eof => {}
                         AVBSFError::Reason(_) => {}
                     },
                 }
             }
-            // Read frame from I/O context.
-            if let Some(mut packet) = self.ctx.read_frame() {
-                let stream_index = packet.stream_index as usize;
-                // Convert pts, dts, duratin to user specified.
-                if let (Some(out_time_base), Some(stream)) =
-                    (self.time_base, self.ctx.streams().get(stream_index))
-                {
-                    let in_time_base = stream.time_base;
-                    let pts = unsafe {
-                        av_rescale_q_rnd(
-                            packet.pts,
-                            in_time_base,
-                            out_time_base,
-                            AVRounding::new().near_inf().pass_min_max(),
-                        )
-                    };
-                    let dts = unsafe {
-                        av_rescale_q_rnd(
-                            packet.dts,
-                            in_time_base,
-                            out_time_base,
-                            AVRounding::new().near_inf().pass_min_max(),
-                        )
+            // All filters drained after the end of the input.
+            if self.draining {
+                return Err(AVReadError::Eof);
+            }
+            let mut packet = match self.pending_packet.take() {
+                Some(packet) => packet,
+                None => {
+                    // Read frame from I/O context.
+                    let mut packet = match self.ctx.try_read_frame() {
+                        Ok(packet) => packet,
+                        Err(AVReadError::Eof) => {
+                            // Drain the packets buffered in the filters.
+                            for bsf in self.bsfs.iter_mut() {
+                                let _ = bsf.send_eof();
+                            }
+                            self.draining = true;
+                            continue;
+                        }
+                        Err(err) => return Err(err),
                     };
-                    let duration =
-                        unsafe { av_rescale_q(packet.duration, in_time_base, out_time_base) };
-                    packet.pts = pts;
-                    packet.dts = dts;
-                    packet.duration = duration;
+                    let stream_index = packet.stream_index as usize;
+                    self.update_stats(&packet);
+                    if self.normalize_timestamps {
+                        self.normalize(&mut packet);
+                    }
+                    // Convert pts, dts, duratin to user specified.
+                    if let (Some(out_time_base), Some(stream)) =
+                        (self.time_base, self.ctx.streams().get(stream_index))
+                    {
+                        rescale_packet(&mut packet, stream.time_base, out_time_base);
+                    }
+                    packet
                 }
-                // Send to bitstream filter.
-                if self.bsfs[stream_index].send_packet(&mut packet).is_err() {
-                    break 'outer;
+            };
+            // Send to bitstream filter.
+            let stream_index = packet.stream_index as usize;
+            match self.bsfs[stream_index].send_packet(&mut packet) {
+                Ok(_) | Err(AVBSFError::Eof) => {}
+                // The filter is full, its output is received first.
+                Err(AVBSFError::Again) => self.pending_packet = Some(packet),
+                Err(AVBSFError::Reason(reason)) => return Err(AVReadError::Reason(reason)),
+            }
+        }
+    }
+
+    /// Returns the statistics of the packets read.
+    pub fn stats(&self) -> ReaderStats {
+        self.stats.clone()
+    }
+
+    /// Subtract the start time from the timestamps of the packet.
+    ///
+    /// The same start time is subtracted from all streams to keep them in sync,
+    /// the streams start later than the others remain nonzero.
+    fn normalize(&mut self, packet: &mut AVPacket) {
+        let time_base = match self.ctx.streams().get(packet.stream_index as usize) {
+            Some(stream) => stream.time_base,
+            None => return,
+        };
+        let start_offset = match self.start_offset {
+            Some(start_offset) => start_offset,
+            None => {
+                // Fallback to the first timestamp if the start time is unknown.
+                let start_time = if self.ctx.start_time != AV_NOPTS_VALUE {
+                    self.ctx.start_time
+                } else if packet.dts != AV_NOPTS_VALUE {
+                    rescale_ts(packet.dts, time_base, AV_TIME_BASE_Q)
+                } else if packet.pts != AV_NOPTS_VALUE {
+                    rescale_ts(packet.pts, time_base, AV_TIME_BASE_Q)
+                } else {
+                    return;
+                };
+                self.start_offset = Some(start_time);
+                start_time
+            }
+        };
+        let offset = rescale_ts(start_offset, AV_TIME_BASE_Q, time_base);
+        if packet.pts != AV_NOPTS_VALUE {
+            packet.pts -= offset;
+        }
+        if packet.dts != AV_NOPTS_VALUE {
+            packet.dts -= offset;
+        }
+    }
+
+    /// Count the packet in the statistics of the stream.
+    fn update_stats(&mut self, packet: &AVPacket) {
+        let stream_index = packet.stream_index as usize;
+        let time_base = match self.ctx.streams().get(stream_index) {
+            Some(stream) => stream.time_base,
+            None => return,
+        };
+        // The streams may be added while reading, like: mpegts.
+        if self.last_pts.len() <= stream_index {
+            self.stats
+                .streams
+                .resize(stream_index + 1, Default::default());
+            self.last_pts.resize(stream_index + 1, AV_NOPTS_VALUE);
+        }
+        let stats = &mut self.stats.streams[stream_index];
+        let last_pts = &mut self.last_pts[stream_index];
+        stats.packets += 1;
+        stats.bytes += packet.size.max(0) as u64;
+        if packet.pts != AV_NOPTS_VALUE {
+            let pts = rescale_ts(packet.pts, time_base, AV_TIME_BASE_Q);
+            if *last_pts != AV_NOPTS_VALUE {
+                let delta = pts - *last_pts;
+                let threshold = self.discontinuity_threshold.as_micros() as i64;
+                // The pts of the frames with B-frames may go backward a little.
+                if delta < -threshold || delta > threshold {
+                    stats.discontinuities += 1;
                 }
-            } else {
-                break 'outer;
             }
+            *last_pts = pts;
         }
+    }
 
-        None
+    /// Returns the number of streams in the file.
+    pub fn nb_streams(&self) -> usize {
+        self.ctx.nb_streams as usize
     }
 
     /// Returns the position of the first frame of the component.
@@ -191,8 +770,1030 @@ impl SimpleReader {
         self.streams().get(index).copied()
     }
 
+    /// Returns the time base of the packets of the stream, the time unit if specified.
+    fn packet_time_base(&self, index: usize) -> Option<AVRational> {
+        self.stream(index)
+            .map(|x| self.time_base.unwrap_or(x.time_base))
+    }
+
+    /// Returns the metadata of the stream at index, like: `language`, `timecode`.
+    pub fn stream_metadata(&self, index: usize) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        if let Some(stream) = self.stream(index) {
+            unsafe {
+                let mut entry: *mut AVDictionaryEntry = std::ptr::null_mut();
+                loop {
+                    entry = av_dict_get(
+                        stream.metadata,
+                        b"\0".as_ptr() as *const _,
+                        entry,
+                        AV_DICT_IGNORE_SUFFIX as _,
+                    );
+                    if entry.is_null() {
+                        break;
+                    }
+                    metadata.insert(
+                        CStr::from_ptr((*entry).key).to_string_lossy().into_owned(),
+                        CStr::from_ptr((*entry).value)
+                            .to_string_lossy()
+                            .into_owned(),
+                    );
+                }
+            }
+        }
+        metadata
+    }
+
     /// Returns a list of all streams in the file.
     pub fn streams(&self) -> &[&AVStream] {
         self.ctx.streams()
     }
+
+    /// Set which packets of the stream to discard by the demuxer, like:
+    /// `AVDISCARD_ALL` to skip the stream entirely.
+    pub fn set_discard(&mut self, stream_index: usize, discard: AVDiscard) -> AVResult<()> {
+        if stream_index >= self.nb_streams() {
+            return Err(format!("Stream #{} does not exists", stream_index).into());
+        }
+        unsafe {
+            (**self.ctx.streams.add(stream_index)).discard = discard;
+        }
+        Ok(())
+    }
+
+    /// Discard all streams except the `stream_indexes`, the packets of them
+    /// are never returned by `read_frame()`.
+    ///
+    /// Reduces the CPU usage of the inputs with many streams.
+    pub fn keep_streams(&mut self, stream_indexes: &[usize]) -> AVResult<()> {
+        for index in 0..self.nb_streams() {
+            let discard = if stream_indexes.contains(&index) {
+                AVDiscard::AVDISCARD_DEFAULT
+            } else {
+                AVDiscard::AVDISCARD_ALL
+            };
+            self.set_discard(index, discard)?;
+        }
+        Ok(())
+    }
+}
+
+/// The protocols allowed by default when opening an url.
+///
+/// The urls may come from the untrusted users, the local files (`file`) or the
+/// protocols reading them (`concat`, `subfile`) are not allowed to avoid the
+/// server side request forgery (SSRF), like: `file:/etc/passwd`.
+pub const DEFAULT_PROTOCOL_WHITELIST: &str =
+    "http,https,httpproxy,tls,tcp,udp,rtp,srtp,rtmp,rtmps,rtmpt,rtmpts,crypto";
+
+/// Returns the format options with the protocol restrictions, the default
+/// whitelist is applied if neither specified.
+fn protocol_options(
+    format_options: Option<&str>,
+    whitelist: Option<&str>,
+    blacklist: Option<&str>,
+) -> AVResult<String> {
+    let mut options = format_options.unwrap_or("").to_owned();
+    let dict = AVDictionaryOwned::from_str(&options)?;
+    let mut push = |key: &str, value: &str| {
+        if !options.is_empty() {
+            options.push(':');
+        }
+        let _ = write!(options, "{}={}", key, value);
+    };
+    match (whitelist, blacklist) {
+        (None, None) => {
+            if dict.get("protocol_whitelist").is_none() && dict.get("protocol_blacklist").is_none()
+            {
+                push("protocol_whitelist", DEFAULT_PROTOCOL_WHITELIST);
+            }
+        }
+        (whitelist, blacklist) => {
+            if let Some(whitelist) = whitelist {
+                push("protocol_whitelist", whitelist);
+            }
+            if let Some(blacklist) = blacklist {
+                push("protocol_blacklist", blacklist);
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Build the `ffconcat` list of the files for the `concat` demuxer.
+fn concat_list<P>(paths: &[P]) -> AVResult<String>
+where
+    P: AsRef<Path>,
+{
+    if paths.is_empty() {
+        return Err("No input files to concat".into());
+    }
+    let mut list = String::from("ffconcat version 1.0\n");
+    for path in paths {
+        let path = path.as_ref();
+        let path = path
+            .to_str()
+            .ok_or_else(|| format!("Invalid path: {:?}", path))?;
+        // Quote the path and escape the quotes inside.
+        let _ = writeln!(list, "file '{}'", path.replace('\'', "'\\''"));
+    }
+    Ok(list)
+}
+
+/// Format the duration in `AV_TIME_BASE` to `HH:MM:SS.xx`.
+fn format_time(duration: i64) -> String {
+    if duration == AV_NOPTS_VALUE {
+        return "N/A".into();
+    }
+    let base = AV_TIME_BASE as i64;
+    let secs = duration / base;
+    let us = duration % base;
+    format!(
+        "{:02}:{:02}:{:02}.{:02}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60,
+        (100 * us) / base
+    )
+}
+
+/// Format the start time in `AV_TIME_BASE` to seconds.
+fn format_start(start_time: i64) -> String {
+    if start_time == AV_NOPTS_VALUE {
+        return "N/A".into();
+    }
+    let base = AV_TIME_BASE as i64;
+    format!(
+        "{}{}.{:06}",
+        if start_time < 0 { "-" } else { "" },
+        start_time.abs() / base,
+        start_time.abs() % base
+    )
+}
+
+/// Format the bitrate in bit/s to `kb/s`.
+fn format_bit_rate(bit_rate: i64) -> String {
+    if bit_rate > 0 {
+        format!("{} kb/s", bit_rate / 1000)
+    } else {
+        "N/A".into()
+    }
+}
+
+/// Options Builder for the SimpleReader.
+#[derive(Clone, Debug, Default)]
+pub struct ReaderOptions {
+    format_options: Option<String>,
+    time_unit: Option<i32>,
+    interrupt: Option<Arc<AtomicBool>>,
+    read_timeout: Option<Duration>,
+    protocol_whitelist: Option<String>,
+    protocol_blacklist: Option<String>,
+    discontinuity_threshold: Option<Duration>,
+    probesize: Option<u64>,
+    analyzeduration: Option<Duration>,
+    nobuffer: Option<bool>,
+    skip_stream_info: Option<bool>,
+    input_format: Option<String>,
+    skip_bsf: Option<bool>,
+    normalize_timestamps: Option<bool>,
+    reconnect: Option<(usize, Duration)>,
+}
+
+impl ReaderOptions {
+    /// Create an new Options Builder for the SimpleReader.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create with the positional arguments of `SimpleReader::open()`.
+    fn with_args(format_options: Option<&str>, time_unit: Option<i32>) -> Self {
+        Self {
+            format_options: format_options.map(String::from),
+            time_unit,
+            ..Default::default()
+        }
+    }
+
+    /// Specified the demuxing format options, like: `rtsp_transport=tcp`.
+    pub fn format_options<S>(mut self, format_options: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.format_options = Some(format_options.into());
+        self
+    }
+
+    /// Maximum size of the data read (in bytes) to detect the streams.
+    ///
+    /// A small value, like: `32768`, reduces the latency of opening the live
+    /// streams, but the streams may be detected without all parameters.
+    pub fn probesize(mut self, probesize: u64) -> Self {
+        self.probesize = Some(probesize);
+        self
+    }
+
+    /// Maximum duration of the data analyzed to detect the streams.
+    pub fn analyzeduration(mut self, analyzeduration: Duration) -> Self {
+        self.analyzeduration = Some(analyzeduration);
+        self
+    }
+
+    /// Reduce the latency by not buffering the packets for probing the
+    /// streams (`AVFMT_FLAG_NOBUFFER`), for live ingest.
+    ///
+    /// The packets are returned as soon as demuxed, at the cost of the
+    /// throughput and the accuracy of the stream information.
+    pub fn nobuffer(mut self, nobuffer: bool) -> Self {
+        self.nobuffer = Some(nobuffer);
+        self
+    }
+
+    /// Skip probing the streams by reading the packets after opening, for the
+    /// formats with the codec parameters in the header, like: mp4.
+    ///
+    /// Opening is faster, but some fields may be left unpopulated, like: the
+    /// frame rate, the duration or the parameters of the streams which are
+    /// only known from the packets (mpegts).
+    pub fn skip_stream_info(mut self, skip_stream_info: bool) -> Self {
+        self.skip_stream_info = Some(skip_stream_info);
+        self
+    }
+
+    /// Force the demuxer by the short name, like: `h264` or `hevc` for the raw
+    /// elementary streams, which can not be detected reliably from the content.
+    ///
+    /// The formats without header need the parameters in the `format_options`,
+    /// like: `rawvideo` with `video_size=352x288:pixel_format=yuv420p`.
+    pub fn input_format(mut self, input_format: &str) -> Self {
+        self.input_format = Some(input_format.to_string());
+        self
+    }
+
+    /// Returns the format options with the typed demuxer options appended.
+    fn demuxer_options(&self) -> Option<String> {
+        let mut options: Vec<String> = self.format_options.iter().cloned().collect();
+        if let Some(probesize) = self.probesize {
+            options.push(format!("probesize={}", probesize));
+        }
+        if let Some(analyzeduration) = self.analyzeduration {
+            options.push(format!("analyzeduration={}", analyzeduration.as_micros()));
+        }
+        let mut options = options.join(":");
+        if let Some(nobuffer) = self.nobuffer {
+            let sign = if nobuffer { '+' } else { '-' };
+            options = append_flag(&options, "fflags", &format!("{}nobuffer", sign));
+        }
+        if options.is_empty() {
+            None
+        } else {
+            Some(options)
+        }
+    }
+
+    /// Convert the pts, dts or duration to specified time unit.
+    pub fn time_unit(mut self, time_unit: i32) -> Self {
+        self.time_unit = Some(time_unit);
+        self
+    }
+
+    /// Subtract the start time from the timestamps, so the output starts at 0.
+    ///
+    /// The same start time is subtracted from all streams to keep them in sync.
+    pub fn normalize_timestamps(mut self, normalize_timestamps: bool) -> Self {
+        self.normalize_timestamps = Some(normalize_timestamps);
+        self
+    }
+
+    /// Output the H264/HEVC frames of mp4 as is (length prefixed), without
+    /// converting to the Annex B format (start code prefixed).
+    pub fn skip_bsf(mut self, skip_bsf: bool) -> Self {
+        self.skip_bsf = Some(skip_bsf);
+        self
+    }
+
+    /// Abort the blocking operations when the flag is set.
+    pub fn interrupt(mut self, interrupt: Arc<AtomicBool>) -> Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    /// Abort the blocking operations when no data received within the duration.
+    pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// The pts jumps beyond the threshold are counted as discontinuities in
+    /// the `stats()`, `DEFAULT_DISCONTINUITY_THRESHOLD` if not specified.
+    pub fn discontinuity_threshold(mut self, threshold: Duration) -> Self {
+        self.discontinuity_threshold = Some(threshold);
+        self
+    }
+
+    /// Only allow the protocols, like: `["http", "https", "tls", "tcp"]`.
+    ///
+    /// The nested protocols must be allowed too, see `DEFAULT_PROTOCOL_WHITELIST`.
+    pub fn protocol_whitelist(mut self, protocols: &[&str]) -> Self {
+        self.protocol_whitelist = Some(protocols.join(","));
+        self
+    }
+
+    /// Disallow the protocols, like: `["file", "concat", "subfile"]`.
+    pub fn protocol_blacklist(mut self, protocols: &[&str]) -> Self {
+        self.protocol_blacklist = Some(protocols.join(","));
+        self
+    }
+
+    /// Reconnect the network input up to `max_retries` times on failures,
+    /// waiting `backoff` before the first retry and doubling it after each
+    /// failed retry, used by the `ReconnectingReader`.
+    pub fn reconnect(mut self, max_retries: usize, backoff: Duration) -> Self {
+        self.reconnect = Some((max_retries, backoff));
+        self
+    }
+
+    /// Open the input file and returns the SimpleReader.
+    ///
+    /// The protocols are not restricted unless specified.
+    pub fn open<P>(self, path: P) -> AVResult<SimpleReader>
+    where
+        P: AsRef<Path> + Sized,
+    {
+        let url = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| format!("Invalid path: {:?}", path.as_ref()))?;
+        let format_options =
+            if self.protocol_whitelist.is_some() || self.protocol_blacklist.is_some() {
+                Some(protocol_options(
+                    self.demuxer_options().as_deref(),
+                    self.protocol_whitelist.as_deref(),
+                    self.protocol_blacklist.as_deref(),
+                )?)
+            } else {
+                self.demuxer_options()
+            };
+        self.open_input(url, format_options.as_deref())
+    }
+
+    /// Open the input url and returns the SimpleReader.
+    ///
+    /// Only the protocols in `DEFAULT_PROTOCOL_WHITELIST` are allowed unless
+    /// the `protocol_whitelist` or `protocol_blacklist` specified.
+    pub fn open_url(self, url: &str) -> AVResult<SimpleReader> {
+        let format_options = protocol_options(
+            self.demuxer_options().as_deref(),
+            self.protocol_whitelist.as_deref(),
+            self.protocol_blacklist.as_deref(),
+        )?;
+        self.open_input(url, Some(&format_options))
+    }
+
+    /// Open the input url and returns the ReconnectingReader.
+    pub fn open_reconnecting(self, url: &str) -> AVResult<ReconnectingReader> {
+        ReconnectingReader::open(url, self)
+    }
+
+    fn open_input(self, url: &str, format_options: Option<&str>) -> AVResult<SimpleReader> {
+        let interrupt = if self.interrupt.is_some() || self.read_timeout.is_some() {
+            Some(AVInterruptState::new(self.interrupt, self.read_timeout))
+        } else {
+            None
+        };
+        let find_stream_info = !self.skip_stream_info.unwrap_or(false);
+        let ctx = AVFormatContextOwned::open_input(
+            url,
+            self.input_format.as_deref(),
+            format_options,
+            interrupt,
+            find_stream_info,
+        )?;
+        let mut reader =
+            SimpleReader::with_context(ctx, self.time_unit, self.skip_bsf.unwrap_or(false))?;
+        if let Some(threshold) = self.discontinuity_threshold {
+            reader.discontinuity_threshold = threshold;
+        }
+        reader.normalize_timestamps = self.normalize_timestamps.unwrap_or(false);
+        if reader.normalize_timestamps && reader.start_time() != AV_NOPTS_VALUE {
+            reader.start_offset = Some(reader.start_time());
+        }
+        Ok(reader)
+    }
+}
+
+/// The default number of retries of the ReconnectingReader.
+pub const DEFAULT_RECONNECT_RETRIES: usize = 5;
+
+/// The default wait before the first retry of the ReconnectingReader.
+pub const DEFAULT_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The maximum wait between the retries of the ReconnectingReader.
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Returns `true` if the url is read by a network protocol, like: `rtmp://`.
+fn is_network_url(url: &str) -> bool {
+    match url.find("://") {
+        Some(pos) => !matches!(&url[..pos], "file" | "pipe"),
+        None => false,
+    }
+}
+
+/// Returns the wait before the retry, doubled after each failed retry.
+fn reconnect_backoff(backoff: Duration, retry: usize) -> Duration {
+    backoff
+        .checked_mul(1 << retry.min(16) as u32)
+        .unwrap_or(MAX_RECONNECT_BACKOFF)
+        .min(MAX_RECONNECT_BACKOFF)
+}
+
+/// Reader of the live network streams, reconnects on failures.
+///
+/// The url is reopened on the read errors, timeouts or the end of the input,
+/// up to `max_retries` times in a row with the backoff, see
+/// `ReaderOptions::reconnect()`. The timestamps of the new connection are
+/// rebased to continue from the end of the last packet, so they increase
+/// monotonically across the reconnects.
+///
+/// The inputs of files are not reconnected, the end of the input returned.
+pub struct ReconnectingReader {
+    url: String,
+    options: ReaderOptions,
+    reader: Option<SimpleReader>,
+    max_retries: usize,
+    backoff: Duration,
+    /// Number of the failed retries in a row.
+    retries: usize,
+    /// Number of the successful reconnects.
+    reconnects: usize,
+    /// Rebase the timestamps on the first packet of the new connection.
+    rebase_pending: bool,
+    /// Offset added to the timestamps in `AV_TIME_BASE`.
+    offset: i64,
+    /// End of the last packet in `AV_TIME_BASE`, after the offset added.
+    next_ts: i64,
+}
+
+impl Debug for ReconnectingReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ReconnectingReader @ 0x{:p}", self)
+    }
+}
+
+impl ReconnectingReader {
+    /// Open the url, fails if the first connection can't be established.
+    pub fn open(url: &str, options: ReaderOptions) -> AVResult<Self> {
+        let (max_retries, backoff) = options
+            .reconnect
+            .unwrap_or((DEFAULT_RECONNECT_RETRIES, DEFAULT_RECONNECT_BACKOFF));
+        let reader = Self::open_reader(url, options.clone())?;
+        Ok(Self {
+            url: url.to_owned(),
+            options,
+            reader: Some(reader),
+            max_retries,
+            backoff,
+            retries: 0,
+            reconnects: 0,
+            rebase_pending: false,
+            offset: 0,
+            next_ts: AV_NOPTS_VALUE,
+        })
+    }
+
+    fn open_reader(url: &str, options: ReaderOptions) -> AVResult<SimpleReader> {
+        if is_network_url(url) {
+            options.open_url(url)
+        } else {
+            options.open(url)
+        }
+    }
+
+    /// Returns the reader of the current connection, `None` if reconnecting.
+    pub fn reader(&self) -> Option<&SimpleReader> {
+        self.reader.as_ref()
+    }
+
+    /// Returns the number of the successful reconnects.
+    pub fn reconnects(&self) -> usize {
+        self.reconnects
+    }
+
+    /// Return the next frame of a stream.
+    pub fn read_frame(&mut self) -> Option<AVPacketOwned> {
+        self.try_read_frame().ok()
+    }
+
+    /// Return the next frame of a stream, reconnect if the reading failed.
+    ///
+    /// The error of the last retry returned if all the retries failed, or the
+    /// error of the reading if the `max_retries` is zero. The
+    /// `AVReadError::Interrupted` returned immediately without retrying.
+    pub fn try_read_frame(&mut self) -> Result<AVPacketOwned, AVReadError> {
+        loop {
+            let reader = match self.reader {
+                Some(ref mut reader) => reader,
+                // All the retries failed on the previous read.
+                None => return Err(AVReadError::Eof),
+            };
+            match reader.try_read_frame() {
+                Ok(mut packet) => {
+                    self.retries = 0;
+                    self.rebase(&mut packet);
+                    return Ok(packet);
+                }
+                Err(AVReadError::Interrupted) => return Err(AVReadError::Interrupted),
+                Err(err) => {
+                    if !is_network_url(&self.url) {
+                        return Err(err);
+                    }
+                    self.reader = None;
+                    self.reconnect(err)?;
+                }
+            }
+        }
+    }
+
+    /// Reopen the url until connected or all the retries failed, the `err`
+    /// of the reading returned if no retry allowed.
+    fn reconnect(&mut self, err: AVReadError) -> Result<(), AVReadError> {
+        if self.retries >= self.max_retries {
+            return Err(err);
+        }
+        loop {
+            std::thread::sleep(reconnect_backoff(self.backoff, self.retries));
+            self.retries += 1;
+            match Self::open_reader(&self.url, self.options.clone()) {
+                Ok(reader) => {
+                    self.reader = Some(reader);
+                    self.reconnects += 1;
+                    self.rebase_pending = true;
+                    return Ok(());
+                }
+                Err(err) => {
+                    if self.retries >= self.max_retries {
+                        return Err(AVReadError::Reason(err.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add the offset to the timestamps to continue from the last packet.
+    fn rebase(&mut self, packet: &mut AVPacket) {
+        let time_base = match self
+            .reader
+            .as_ref()
+            .and_then(|x| x.packet_time_base(packet.stream_index as usize))
+        {
+            Some(time_base) => time_base,
+            None => return,
+        };
+        let ts = if packet.dts != AV_NOPTS_VALUE {
+            packet.dts
+        } else {
+            packet.pts
+        };
+        if ts == AV_NOPTS_VALUE {
+            return;
+        }
+        let ts = rescale_ts(ts, time_base, AV_TIME_BASE_Q);
+        if self.rebase_pending {
+            self.rebase_pending = false;
+            if self.next_ts != AV_NOPTS_VALUE {
+                self.offset = self.next_ts - ts;
+            }
+        }
+        let offset = rescale_ts(self.offset, AV_TIME_BASE_Q, time_base);
+        if packet.pts != AV_NOPTS_VALUE {
+            packet.pts += offset;
+        }
+        if packet.dts != AV_NOPTS_VALUE {
+            packet.dts += offset;
+        }
+        let duration = rescale_ts(packet.duration.max(0), time_base, AV_TIME_BASE_Q);
+        let end = ts + self.offset + duration;
+        if self.next_ts == AV_NOPTS_VALUE || end > self.next_ts {
+            self.next_ts = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy::fixture::{example_frames, write_raw_es};
+    use crate::easy::{SimpleWriter, VideoDesc, Writer};
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "00:00:00.00");
+        assert_eq!(format_time(3_723_450_000), "01:02:03.45");
+        assert_eq!(format_time(AV_NOPTS_VALUE), "N/A");
+        assert_eq!(format_start(-1_500_000), "-1.500000");
+        assert_eq!(format_bit_rate(128_000), "128 kb/s");
+    }
+
+    #[test]
+    fn test_stats() {
+        // Returns the statistics after reading all the packets.
+        fn read_stats(options: ReaderOptions, path: &str) -> ReaderStats {
+            let mut reader = options.open(path).unwrap();
+            while reader.read_frame().is_some() {}
+            reader.stats()
+        }
+
+        let path = "/tmp/ffav-stats.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], Some("mpegts"), None).unwrap();
+        for (frame_bytes, index) in example_frames().zip(0..) {
+            // The pts jumps forward 20 seconds from the 100th frame.
+            let pts = index * 40000 + if index >= 100 { 20_000_000 } else { 0 };
+            writer
+                .write_bytes(frame_bytes, pts, 40000, index == 0, 0)
+                .unwrap();
+        }
+        Box::new(writer).finish().unwrap();
+        let num_frames = example_frames().count() as u64;
+
+        let stats = read_stats(ReaderOptions::new(), path);
+        assert_eq!(stats.streams.len(), 1);
+        assert_eq!(stats.streams[0].packets, num_frames);
+        assert_eq!(stats.streams[0].discontinuities, 1);
+        assert_eq!(stats.packets(), num_frames);
+        assert!(stats.bytes() > 0);
+        assert_eq!(stats.bytes(), stats.streams[0].bytes);
+
+        let options = ReaderOptions::new().discontinuity_threshold(Duration::from_secs(30));
+        let stats = read_stats(options, path);
+        assert_eq!(stats.streams[0].packets, num_frames);
+        assert_eq!(stats.streams[0].discontinuities, 0);
+    }
+
+    #[test]
+    fn test_dump_to_string() {
+        let path = "/tmp/ffav-dump.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, pts == 0, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let reader = SimpleReader::open(path, None, None).unwrap();
+        let dump = reader.dump_to_string();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            format!("Input #0, {}, from '{}':", reader.format_name(), path)
+        );
+        assert!(lines[1].starts_with("  Duration: 00:00:"));
+        assert!(lines[2].starts_with("    Stream #0:0: Video: h264, 352x288"));
+    }
+
+    #[test]
+    fn test_protocol_options() {
+        assert_eq!(
+            protocol_options(Some("rtsp_transport=tcp"), None, None).unwrap(),
+            format!(
+                "rtsp_transport=tcp:protocol_whitelist={}",
+                DEFAULT_PROTOCOL_WHITELIST
+            )
+        );
+        assert_eq!(
+            protocol_options(Some("protocol_whitelist=file"), None, None).unwrap(),
+            "protocol_whitelist=file"
+        );
+        assert_eq!(
+            protocol_options(None, Some("http,tcp"), Some("file")).unwrap(),
+            "protocol_whitelist=http,tcp:protocol_blacklist=file"
+        );
+    }
+
+    #[test]
+    fn test_demuxer_options() {
+        let options = ReaderOptions::new()
+            .format_options("rtsp_transport=tcp")
+            .probesize(32768)
+            .analyzeduration(Duration::from_millis(500))
+            .nobuffer(true);
+        assert_eq!(
+            options.demuxer_options().as_deref(),
+            Some("rtsp_transport=tcp:probesize=32768:analyzeduration=500000:fflags=+nobuffer")
+        );
+        assert_eq!(ReaderOptions::new().demuxer_options(), None);
+        let options = ReaderOptions::new()
+            .format_options("fflags=+genpts:rtsp_transport=tcp")
+            .nobuffer(true);
+        assert_eq!(
+            options.demuxer_options().as_deref(),
+            Some("fflags=+genpts+nobuffer:rtsp_transport=tcp")
+        );
+    }
+
+    #[test]
+    fn test_probesize() {
+        // Returns the position of the input after probing the streams.
+        fn probed_bytes(options: ReaderOptions, path: &str) -> i64 {
+            let reader = options.open(path).unwrap();
+            unsafe { avio_seek(reader.ctx.pb, 0, libc::SEEK_CUR) }
+        }
+
+        let path = "/tmp/ffav-probesize.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], Some("mpegts"), None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, pts == 0, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        // The frame rate is estimated from the first 20 frames by default.
+        let full = probed_bytes(ReaderOptions::new(), path);
+        let tiny = probed_bytes(ReaderOptions::new().probesize(32), path);
+        assert!(tiny > 0 && tiny < full);
+    }
+
+    #[test]
+    fn test_reconnect_options() {
+        assert!(is_network_url("rtmp://127.0.0.1/live/stream"));
+        assert!(is_network_url("https://example.com/live.m3u8"));
+        assert!(!is_network_url("file:///tmp/a.ts"));
+        assert!(!is_network_url("/tmp/a.ts"));
+        let backoff = Duration::from_secs(1);
+        assert_eq!(reconnect_backoff(backoff, 0), backoff);
+        assert_eq!(reconnect_backoff(backoff, 2), Duration::from_secs(4));
+        assert_eq!(reconnect_backoff(backoff, 100), MAX_RECONNECT_BACKOFF);
+    }
+
+    #[test]
+    fn test_reconnecting_reader() {
+        // Serves the file on the `connections` and stops listening, returns the url.
+        fn serve(path: &'static str, connections: usize) -> String {
+            use std::io::Write as _;
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("tcp://{}", listener.local_addr().unwrap());
+            std::thread::spawn(move || {
+                for stream in listener.incoming().take(connections) {
+                    let _ = stream.unwrap().write_all(&std::fs::read(path).unwrap());
+                }
+            });
+            url
+        }
+
+        let path = "/tmp/ffav-reconnect.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], Some("mpegts"), None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, pts == 0, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let num_frames = example_frames().count();
+
+        // The end of the input returned without retrying.
+        let mut reader = ReaderOptions::new()
+            .reconnect(0, Duration::from_millis(100))
+            .open_reconnecting(&serve(path, 1))
+            .unwrap();
+        let mut count = 0;
+        let err = loop {
+            match reader.try_read_frame() {
+                Ok(_) => count += 1,
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(err, AVReadError::Eof));
+        assert_eq!(count, num_frames);
+        assert_eq!(reader.reconnects(), 0);
+
+        // Reconnected once, the retry after the listener stopped fails.
+        let mut reader = ReaderOptions::new()
+            .reconnect(1, Duration::from_millis(100))
+            .open_reconnecting(&serve(path, 2))
+            .unwrap();
+        let mut count = 0;
+        let mut last_dts = i64::MIN;
+        let err = loop {
+            match reader.try_read_frame() {
+                Ok(packet) => {
+                    assert!(packet.dts > last_dts);
+                    last_dts = packet.dts;
+                    count += 1;
+                }
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(err, AVReadError::Reason(_)));
+        assert_eq!(count, num_frames * 2);
+        assert_eq!(reader.reconnects(), 1);
+    }
+
+    #[test]
+    fn test_input_format() {
+        let path = "/tmp/ffav-raw-es.bin";
+        write_raw_es(path);
+        let mut reader = ReaderOptions::new()
+            .input_format("h264")
+            .open(path)
+            .unwrap();
+        let par = reader.streams()[0].codecpar().unwrap();
+        assert_eq!(par.codec_id, AVCodecID::AV_CODEC_ID_H264);
+        assert_eq!(par.codec_tag, 0);
+        let packet = reader.read_frame().unwrap();
+        assert_eq!(&packet.data()[..4], &[0, 0, 0, 1]);
+        assert!(ReaderOptions::new()
+            .input_format("no-such-format")
+            .open(path)
+            .is_err());
+    }
+
+    #[test]
+    fn test_termination_reason() {
+        let path = "/tmp/ffav-termination.264";
+        write_raw_es(path);
+        let mut reader = ReaderOptions::new()
+            .input_format("h264")
+            .open(path)
+            .unwrap();
+        assert_eq!(reader.termination_reason(), None);
+        while reader.read_frame().is_some() {}
+        assert_eq!(reader.termination_reason(), Some(TerminationReason::Eof));
+        reader.seek(Duration::from_secs(0)).unwrap();
+        assert_eq!(reader.termination_reason(), None);
+
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let mut reader = ReaderOptions::new()
+            .input_format("h264")
+            .interrupt(interrupt.clone())
+            .open(path)
+            .unwrap();
+        assert!(reader.read_frame().is_some());
+        interrupt.store(true, Ordering::SeqCst);
+        while reader.read_frame().is_some() {}
+        assert_eq!(
+            reader.termination_reason(),
+            Some(TerminationReason::Interrupted)
+        );
+    }
+
+    #[test]
+    fn test_reader_normalize_timestamps() {
+        let path = "/tmp/ffav-normalize.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer =
+            SimpleWriter::new(path, &[&v_desc], Some("mpegts"), Some("mpegts_copyts=1")).unwrap();
+        // Starts at 1 hour, like: the recorders use the wall clock.
+        let mut pts = 3600 * 1000000;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let mut reader = ReaderOptions::new()
+            .normalize_timestamps(true)
+            .open(path)
+            .unwrap();
+        let (_, info) = reader.frames().next().unwrap();
+        assert!(info.pts.unwrap() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_reader_seek_byte() {
+        let path = "/tmp/ffav-seek-byte.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], Some("mpegts"), None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let mut reader = SimpleReader::open(path, None, None).unwrap();
+        let total = reader.frames().count();
+        // Lands in the middle of a packet, not aligned to the TS packets.
+        let size = std::fs::metadata(path).unwrap().len() as i64;
+        reader.seek_byte(size / 2 + 7).unwrap();
+        let rest = reader.frames().count();
+        assert!(rest > 0 && rest < total);
+    }
+
+    #[test]
+    fn test_keyframe_index() {
+        let path = "/tmp/ffav-keyframe-index.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, pts == 0, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let mut reader = SimpleReader::open(path, None, None).unwrap();
+        let total = reader.frames().count();
+        let index = reader.build_keyframe_index().unwrap();
+        assert!(!index.is_empty());
+        assert!(index.windows(2).all(|x| x[0].0 < x[1].0 && x[0].1 < x[1].1));
+        // Rewound to the start.
+        assert_eq!(reader.frames().count(), total);
+    }
+
+    #[test]
+    fn test_reader_skip_stream_info() {
+        let path = "/tmp/ffav-skip-stream-info.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let mut reader = SimpleReader::open(path, None, None).unwrap();
+        let total = reader.frames().count();
+        let mut reader = ReaderOptions::new()
+            .skip_stream_info(true)
+            .open(path)
+            .unwrap();
+        let par = reader.stream(0).unwrap().codecpar().unwrap();
+        assert_eq!(par.codec_id, AVCodecID::AV_CODEC_ID_H264);
+        assert_eq!((par.width, par.height), (352, 288));
+        assert_eq!(reader.frames().count(), total);
+    }
+
+    #[test]
+    fn test_reader_keep_streams() {
+        let path = "/tmp/ffav-keep-streams.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer =
+            SimpleWriter::new(path, &[&v_desc, &v_desc], Some("mpegts"), None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            for stream_index in 0..2 {
+                writer
+                    .write_bytes(frame_bytes, pts, 40000, false, stream_index)
+                    .unwrap();
+            }
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let mut reader = SimpleReader::open(path, None, None).unwrap();
+        assert_eq!(reader.nb_streams(), 2);
+        reader.keep_streams(&[1]).unwrap();
+        let mut frames = 0;
+        while let Some(packet) = reader.read_frame() {
+            assert_eq!(packet.stream_index, 1);
+            frames += 1;
+        }
+        assert!(frames > 0);
+        assert!(reader.set_discard(2, AVDiscard::AVDISCARD_ALL).is_err());
+    }
+
+    #[test]
+    fn test_input_format_rawvideo() {
+        // Two gray frames of yuv420p.
+        let path = "/tmp/ffav-raw-video.yuv";
+        std::fs::write(path, vec![128u8; 352 * 288 * 3 / 2 * 2]).unwrap();
+        let mut reader = ReaderOptions::new()
+            .input_format("rawvideo")
+            .format_options("video_size=352x288:pixel_format=yuv420p")
+            .open(path)
+            .unwrap();
+        let par = reader.streams()[0].codecpar().unwrap();
+        assert_eq!(par.codec_id, AVCodecID::AV_CODEC_ID_RAWVIDEO);
+        assert_eq!((par.width, par.height), (352, 288));
+        let mut frames = 0;
+        while reader.read_frame().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 2);
+        assert!(AVFormatContextOwned::with_input_format(path, "no-such-format", None).is_err());
+    }
+
+    #[test]
+    fn test_concat_list() {
+        let list = concat_list(&["/tmp/a-0.ts", "/tmp/it's-1.ts"]).unwrap();
+        assert_eq!(
+            list,
+            "ffconcat version 1.0\nfile '/tmp/a-0.ts'\nfile '/tmp/it'\\''s-1.ts'\n"
+        );
+        assert!(concat_list::<&str>(&[]).is_err());
+    }
 }
@@ -0,0 +1,145 @@
+use crate::ffi::*;
+use std::convert::TryInto;
+use std::time::Duration;
+
+/// The time base of the nanoseconds.
+pub(crate) const NANOS_TIME_BASE: AVRational = AVRational {
+    num: 1,
+    den: 1_000_000_000,
+};
+
+/// Arithmetic and conversion of the rational numbers, like: time bases or frame rates.
+///
+/// The `From<AVRational> for f64` can't be implemented here, both types are
+/// foreign to this crate (the orphan rule), use `as_f64()` instead.
+pub trait AVRationalExt {
+    /// Returns the value as a floating point number, `NaN` if the `den` is 0.
+    fn as_f64(&self) -> f64;
+
+    /// Returns the reciprocal, like: the frame duration of a frame rate.
+    fn inverse(&self) -> AVRational;
+
+    /// Returns the reduced product of two rationals.
+    fn mul(&self, other: AVRational) -> AVRational;
+}
+
+impl AVRationalExt for AVRational {
+    fn as_f64(&self) -> f64 {
+        if self.den == 0 {
+            f64::NAN
+        } else {
+            self.num as f64 / self.den as f64
+        }
+    }
+
+    fn inverse(&self) -> AVRational {
+        AVRational {
+            num: self.den,
+            den: self.num,
+        }
+    }
+
+    fn mul(&self, other: AVRational) -> AVRational {
+        unsafe { av_mul_q(*self, other) }
+    }
+}
+
+/// Rescale a timestamp from a time base to another.
+///
+/// The `AV_NOPTS_VALUE` means unknown timestamp, it's returned unchanged.
+pub fn rescale_ts(ts: i64, from: AVRational, to: AVRational) -> i64 {
+    if ts == AV_NOPTS_VALUE {
+        ts
+    } else {
+        unsafe { av_rescale_q_rnd(ts, from, to, AVRounding::new().near_inf().pass_min_max()) }
+    }
+}
+
+/// Rescale the pts, dts and duration of the packet from a time base to another.
+///
+/// The unknown timestamps (`AV_NOPTS_VALUE`) are kept, the duration is
+/// rescaled as is, including the negative ones and the ones shorter than
+/// the target time base which are rounded to 0.
+pub fn rescale_packet(pkt: &mut AVPacket, from: AVRational, to: AVRational) {
+    pkt.pts = rescale_ts(pkt.pts, from, to);
+    pkt.dts = rescale_ts(pkt.dts, from, to);
+    pkt.duration = unsafe { av_rescale_q(pkt.duration, from, to) };
+}
+
+/// Convert the timestamp in `time_base` to Duration.
+///
+/// Returns `None` if the timestamp is unknown (`AV_NOPTS_VALUE`), negative or
+/// the time base is invalid.
+pub fn pts_to_duration(pts: i64, time_base: AVRational) -> Option<Duration> {
+    if pts == AV_NOPTS_VALUE || pts < 0 || time_base.den == 0 {
+        return None;
+    }
+    let ns = rescale_ts(pts, time_base, NANOS_TIME_BASE);
+    Some(Duration::from_nanos(ns as u64))
+}
+
+/// Convert the Duration to the timestamp in `time_base`, rounded to the nearest.
+pub fn duration_to_pts(duration: Duration, time_base: AVRational) -> i64 {
+    match duration.as_nanos().try_into() {
+        Ok(ns) => rescale_ts(ns, NANOS_TIME_BASE, time_base),
+        // Beyond the range of the nanoseconds in i64, about 292 years.
+        Err(_) => rescale_ts(duration.as_secs() as i64, AVRational::new(1, 1), time_base),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rescale_nopts() {
+        let from = AVRational::new(1, 1000000);
+        let to = AVRational::new(1, 90000);
+        assert_eq!(rescale_ts(AV_NOPTS_VALUE, from, to), AV_NOPTS_VALUE);
+        assert_eq!(rescale_ts(1000000, from, to), 90000);
+    }
+
+    #[test]
+    fn test_rescale_packet() {
+        let mut pkt: AVPacket = unsafe { std::mem::zeroed() };
+        pkt.pts = 1000000;
+        pkt.dts = AV_NOPTS_VALUE;
+        pkt.duration = 1;
+        rescale_packet(
+            &mut pkt,
+            AVRational::new(1, 1000000),
+            AVRational::new(1, 1000),
+        );
+        assert_eq!((pkt.pts, pkt.dts, pkt.duration), (1000, AV_NOPTS_VALUE, 0));
+        pkt.duration = -40;
+        rescale_packet(
+            &mut pkt,
+            AVRational::new(1, 1000),
+            AVRational::new(1, 90000),
+        );
+        assert_eq!((pkt.pts, pkt.duration), (90000, -3600));
+    }
+
+    #[test]
+    fn test_pts_to_duration() {
+        let time_base = AVRational::new(1, 90000);
+        assert_eq!(
+            pts_to_duration(90000, time_base),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(pts_to_duration(AV_NOPTS_VALUE, time_base), None);
+        assert_eq!(pts_to_duration(-3600, time_base), None);
+        assert_eq!(duration_to_pts(Duration::from_millis(40), time_base), 3600);
+    }
+
+    #[test]
+    fn test_rational_ext() {
+        let frame_rate = AVRational::new(30000, 1001);
+        assert!((frame_rate.as_f64() - 29.97).abs() < 0.01);
+        let frame_duration = frame_rate.inverse();
+        assert_eq!((frame_duration.num, frame_duration.den), (1001, 30000));
+        let product = frame_rate.mul(frame_duration);
+        assert_eq!((product.num, product.den), (1, 1));
+        assert!(AVRational::new(1, 0).as_f64().is_nan());
+    }
+}
@@ -0,0 +1,100 @@
+use crate::ffi::{AVPacketSideDataType::*, *};
+
+/// Typed view of a side data attached to the stream or packet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AVSideData<'a> {
+    /// The rotation angle in degrees from the display matrix,
+    /// counterclockwise in range [-180.0, 180.0].
+    DisplayMatrix(f64),
+    /// Any other side data with the raw bytes.
+    Other(AVPacketSideDataType, &'a [u8]),
+}
+
+impl<'a> AVSideData<'a> {
+    /// Wrap a side data entry.
+    ///
+    /// # Safety
+    /// The `data` and `size` of the entry must be valid for the lifetime `'a`.
+    pub unsafe fn from_raw(sd: &'a AVPacketSideData) -> Self {
+        let data: &'a [u8] = if sd.data.is_null() || sd.size <= 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(sd.data, sd.size as usize)
+        };
+        match sd.type_ {
+            AV_PKT_DATA_DISPLAYMATRIX if data.len() >= 9 * 4 => {
+                let angle = av_display_rotation_get(data.as_ptr() as *const i32);
+                if angle.is_nan() {
+                    AVSideData::Other(sd.type_, data)
+                } else {
+                    AVSideData::DisplayMatrix(angle)
+                }
+            }
+            _ => AVSideData::Other(sd.type_, data),
+        }
+    }
+}
+
+/// Extension for accessing the side data of the stream or packet.
+pub trait SideDataExt {
+    /// Returns a list of all side data.
+    fn side_data_list(&self) -> Vec<AVSideData<'_>>;
+
+    /// Returns the rotation angle in degrees from the display matrix,
+    /// `None` if the display matrix does not exists.
+    fn rotation_degrees(&self) -> Option<f64> {
+        self.side_data_list().into_iter().find_map(|sd| match sd {
+            AVSideData::DisplayMatrix(angle) => Some(angle),
+            _ => None,
+        })
+    }
+}
+
+impl SideDataExt for AVStream {
+    fn side_data_list(&self) -> Vec<AVSideData<'_>> {
+        side_data_list(self.side_data, self.nb_side_data)
+    }
+}
+
+impl SideDataExt for AVPacket {
+    fn side_data_list(&self) -> Vec<AVSideData<'_>> {
+        side_data_list(self.side_data, self.side_data_elems)
+    }
+}
+
+fn side_data_list<'a>(side_data: *const AVPacketSideData, nb: i32) -> Vec<AVSideData<'a>> {
+    if side_data.is_null() || nb <= 0 {
+        return vec![];
+    }
+    unsafe {
+        std::slice::from_raw_parts(side_data, nb as usize)
+            .iter()
+            .map(|sd| AVSideData::from_raw(sd))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy::AVPacketOwned;
+
+    #[test]
+    fn test_packet_side_data() {
+        let mut packet = AVPacketOwned::new();
+        assert!(packet.side_data_list().is_empty());
+        assert_eq!(packet.rotation_degrees(), None);
+        unsafe {
+            let matrix = av_packet_new_side_data(&mut *packet, AV_PKT_DATA_DISPLAYMATRIX, 9 * 4);
+            av_display_rotation_set(matrix as *mut i32, 90.0);
+            av_packet_new_side_data(&mut *packet, AV_PKT_DATA_SKIP_SAMPLES, 10);
+        }
+        let side_data = packet.side_data_list();
+        assert_eq!(side_data.len(), 2);
+        assert_eq!(
+            side_data[1],
+            AVSideData::Other(AV_PKT_DATA_SKIP_SAMPLES, &[0; 10])
+        );
+        assert_eq!(packet.rotation_degrees().map(f64::round), Some(90.0));
+    }
+}
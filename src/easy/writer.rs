@@ -1,9 +1,16 @@
-use super::{owned::*, AVResult};
-use crate::ffi::{AVCodecID::*, AVFieldOrder::*, AVMediaType::*, AVPixelFormat::*, *};
+use super::{borrowed::*, capabilities::*, io::*, owned::*, rescale::*, side_data::*, AVResult};
+use crate::ffi::{AVCodecID::*, AVMediaType::*, AVPixelFormat::*, *};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use std::any::Any;
 use std::convert::TryInto;
+use std::ffi::{CStr, CString};
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, Instant};
 
 /// Trait for Media Description.
@@ -22,6 +29,16 @@ pub trait MediaDesc {
     fn as_video_desc(&self) -> Option<&VideoDesc> {
         None
     }
+
+    /// Cast to SubtitleDesc reference.
+    fn as_subtitle_desc(&self) -> Option<&SubtitleDesc> {
+        None
+    }
+
+    /// Cast to DataDesc reference.
+    fn as_data_desc(&self) -> Option<&DataDesc> {
+        None
+    }
 }
 
 impl Debug for &dyn MediaDesc {
@@ -45,9 +62,12 @@ pub trait Writer {
     /// # Arguments
     /// * `bytes` - Stream byte data.
     /// * `pts` - Timestamp of the frame.
-    /// * `duration` - Duration of the frame.
+    /// * `duration` - Duration of the frame, `0` to let the muxer infer it from the next pts.
     /// * `is_key_frame` - True if is key frame.
     /// * `stream_index` - Index of the stream.
+    ///
+    /// Variable frame rate (VFR) streams are supported, the `pts` and `duration`
+    /// can vary per frame as long as the `pts` increases monotonically.
     fn write_bytes(
         &mut self,
         bytes: &[u8],
@@ -57,17 +77,88 @@ pub trait Writer {
         stream_index: usize,
     ) -> AVResult<()>;
 
+    /// Write frame bytes with the decompression timestamp to the stream.
+    ///
+    /// Used for the streams with reordered frames (e.g. B-frames), like:
+    /// remuxing the packets of `SimpleReader`. The `dts` must increase
+    /// monotonically and not greater than the `pts`.
+    ///
+    /// The writers without reordering support ignore the `dts`.
+    fn write_bytes_with_dts(
+        &mut self,
+        bytes: &[u8],
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        let _ = dts;
+        self.write_bytes(bytes, pts, duration, is_key_frame, stream_index)
+    }
+
+    /// Write the reference counted frame bytes to the stream.
+    ///
+    /// The muxer holds a reference of the `data` instead of copying if supported,
+    /// the `data` is released when the muxer done with it.
+    #[cfg(feature = "bytes")]
+    fn write_bytes_ref(
+        &mut self,
+        data: Bytes,
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.write_bytes(&data, pts, duration, is_key_frame, stream_index)
+    }
+
     /// Write the trailer of the format to the stream.
     fn write_trailer(&mut self) -> AVResult<()>;
 
     /// Close all resouces accessed by the muxer.
     fn close(&mut self);
 
+    /// Write the trailer, flush all buffered data and close the writer.
+    ///
+    /// Unlike `close()` or dropping the writer, the error of writing the
+    /// trailer (e.g. disk full) is returned to the caller.
+    fn finish(mut self: Box<Self>) -> AVResult<()> {
+        self.write_trailer()?;
+        self.flush();
+        Ok(())
+    }
+
     /// Flush all buffered data to stream destionation.
     fn flush(&mut self);
 
     /// Returns the size of the stream processed.
     fn size(&self) -> u64;
+
+    /// Request to start a new fragment, does nothing if not splitting.
+    fn force_split(&mut self) {}
+
+    /// Returns the writer as `Any`, implemented as `self`.
+    ///
+    /// Use `<dyn Writer>::downcast_ref()` to get the concrete type.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns the writer as mutable `Any`, implemented as `self`.
+    ///
+    /// Use `<dyn Writer>::downcast_mut()` to get the concrete type.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl dyn Writer {
+    /// Returns the concrete writer if it is of type `T`, like: `SplitWriter`.
+    pub fn downcast_ref<T: Writer + 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref::<T>()
+    }
+
+    /// Returns the mutable concrete writer if it is of type `T`.
+    pub fn downcast_mut<T: Writer + 'static>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut::<T>()
+    }
 }
 
 impl Debug for &dyn Writer {
@@ -82,26 +173,284 @@ impl Debug for Box<dyn Writer> {
     }
 }
 
+/// The default time base if the time unit is invalid.
+const DEFAULT_TIME_UNIT: i32 = 90000;
+
+/// Returns the time base of the time unit, like: `1/1000000` for `us`,
+/// the `1/90000` returned if the `time_unit` is zero or negative.
+fn time_base_of_unit(time_unit: i32) -> AVRational {
+    if time_unit > 0 {
+        AVRational::with_normalize(time_unit)
+    } else {
+        AVRational::with_normalize(DEFAULT_TIME_UNIT)
+    }
+}
+
+/// Returns error if the time base can't be used for rescaling.
+fn check_time_base(time_base: AVRational) -> AVResult<()> {
+    if time_base.num > 0 && time_base.den > 0 {
+        Ok(())
+    } else {
+        Err(format!("Invalid time base: {}/{}", time_base.num, time_base.den).into())
+    }
+}
+
+/// Returns error if the timecode isn't `HH:MM:SS:FF` (or `HH:MM:SS;FF` for drop
+/// frame) or the frames out of the frame rate.
+fn check_timecode(timecode: &str, frame_rate: AVRational) -> AVResult<()> {
+    let invalid = || format!("Invalid timecode: {}", timecode);
+    if frame_rate.num <= 0 || frame_rate.den <= 0 {
+        return Err(format!("Invalid frame rate: {}/{}", frame_rate.num, frame_rate.den).into());
+    }
+    let parts: Vec<&str> = timecode.split([':', ';']).collect();
+    if parts.len() != 4 || timecode[..timecode.len() - parts[3].len() - 1].contains(';') {
+        return Err(invalid().into());
+    }
+    let mut values = [0u32; 4];
+    for (value, part) in values.iter_mut().zip(parts.iter()) {
+        if part.len() != 2 {
+            return Err(invalid().into());
+        }
+        *value = part.parse().map_err(|_| invalid())?;
+    }
+    let fps = (frame_rate.num + frame_rate.den - 1) / frame_rate.den;
+    if values[0] > 23 || values[1] > 59 || values[2] > 59 || values[3] >= fps as u32 {
+        return Err(invalid().into());
+    }
+    Ok(())
+}
+
+/// Returns `true` if a stream is created for the media by the SimpleWriter,
+/// the medias without codec (like: `AudioDesc::new()`) don't take a `stream_index`.
+fn has_stream(desc: &dyn MediaDesc) -> bool {
+    desc.codec_id() != AV_CODEC_ID_NONE
+}
+
+/// Returns the `stream_index` for writing of each media by the order of the
+/// medias, `None` for the medias without stream.
+fn media_stream_indexes<'a, I>(descs: I) -> Vec<Option<usize>>
+where
+    I: IntoIterator<Item = &'a dyn MediaDesc>,
+{
+    let mut next = 0;
+    descs
+        .into_iter()
+        .map(|desc| {
+            if has_stream(desc) {
+                next += 1;
+                Some(next - 1)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the time base of the timestamps of the media.
+fn media_time_base(desc: &dyn MediaDesc) -> AVRational {
+    if let Some(desc) = desc.as_video_desc() {
+        desc.time_base
+    } else if let Some(desc) = desc.as_subtitle_desc() {
+        desc.time_base
+    } else if let Some(desc) = desc.as_data_desc() {
+        desc.time_base
+    } else if let Some(desc) = desc.as_audio_desc() {
+        if desc.time_base.den > 0 {
+            desc.time_base
+        } else {
+            AVRational::new(1, desc.sample_rate as i32)
+        }
+    } else {
+        AVRational::new(0, 1)
+    }
+}
+
+/// Returns error if the medias or the format options can't be muxed.
+///
+/// Checked before opening the output, so an existing file is not truncated
+/// by an invalid description.
+fn check_medias(descs: &[&dyn MediaDesc], format_options: Option<&str>) -> AVResult<()> {
+    let options = AVDictionaryOwned::from_str(format_options.unwrap_or(""))?;
+    let movflags = options.get("movflags").unwrap_or_default();
+    if has_movflag(&movflags, "faststart")
+        && (has_movflag(&movflags, "frag_keyframe") || has_movflag(&movflags, "empty_moov"))
+    {
+        return Err("The faststart is incompatible with the fragmented mp4".into());
+    }
+    for media in descs.iter().filter(|x| has_stream(**x)) {
+        check_time_base(media_time_base(*media))?;
+        let desc = match media.as_video_desc() {
+            Some(desc) => desc,
+            None => continue,
+        };
+        if let Some(output_time_base) = desc.output_time_base {
+            check_time_base(output_time_base)?;
+        }
+        if let Some(name) = &desc.encoder_name {
+            match encoder_codec_id(name) {
+                Some(codec_id) if codec_id == desc.codec_id => {}
+                Some(_) => {
+                    return Err(format!(
+                        "The encoder {:?} does not encode {:?}",
+                        name, desc.codec_id
+                    )
+                    .into())
+                }
+                None => {
+                    return Err(format!("Encoder {:?} not found in the linked FFmpeg", name).into())
+                }
+            }
+        }
+        if desc.codec_id == AV_CODEC_ID_RAWVIDEO {
+            raw_frame_size(desc)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the size of each frame in bytes of the raw video.
+fn raw_frame_size(desc: &VideoDesc) -> AVResult<usize> {
+    let size = unsafe { av_image_get_buffer_size(desc.pix_fmt, desc.width, desc.height, 1) };
+    if size <= 0 {
+        return Err(format!(
+            "Invalid raw video: {}x{} {:?}",
+            desc.width, desc.height, desc.pix_fmt
+        )
+        .into());
+    }
+    Ok(size as usize)
+}
+
+/// Returns the width and height of the picture, parsed from the `IHDR` of the
+/// PNG or the `SOFn` of the JPEG, `None` if the header is not found.
+fn picture_size(bytes: &[u8], codec_id: AVCodecID) -> Option<(i32, i32)> {
+    let be16 = |pos: usize| Some(u16::from_be_bytes([*bytes.get(pos)?, *bytes.get(pos + 1)?]));
+    match codec_id {
+        AV_CODEC_ID_PNG => {
+            if bytes.len() < 24 || &bytes[..8] != b"\x89PNG\r\n\x1a\n" || &bytes[12..16] != b"IHDR"
+            {
+                return None;
+            }
+            let width = u32::from_be_bytes(bytes[16..20].try_into().ok()?);
+            let height = u32::from_be_bytes(bytes[20..24].try_into().ok()?);
+            Some((width.try_into().ok()?, height.try_into().ok()?))
+        }
+        AV_CODEC_ID_MJPEG => {
+            if bytes.get(..2)? != [0xff, 0xd8] {
+                return None;
+            }
+            let mut pos = 2;
+            loop {
+                if *bytes.get(pos)? != 0xff {
+                    return None;
+                }
+                let marker = *bytes.get(pos + 1)?;
+                match marker {
+                    // Fill bytes before the marker.
+                    0xff => pos += 1,
+                    // The markers without the segment.
+                    0x01 | 0xd0..=0xd7 => pos += 2,
+                    // SOF0 ~ SOF15, except DHT, JPG and DAC.
+                    0xc0..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                        let height = be16(pos + 5)?;
+                        let width = be16(pos + 7)?;
+                        return Some((width as i32, height as i32));
+                    }
+                    // The image data begins, no frame header before it.
+                    0xd9 | 0xda => return None,
+                    _ => pos += 2 + be16(pos + 2)? as usize,
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns `true` if the flag present in the `movflags`, like: `faststart+frag_keyframe`.
+fn has_movflag(movflags: &str, flag: &str) -> bool {
+    movflags.split('+').any(|x| x == flag)
+}
+
+/// Check the path of the `image2` muxer contains the frame number pattern,
+/// like: `frame_%05d.png`, unless a single file is overwritten by `update=1`.
+fn check_image_pattern(ctx: &AVFormatContext, update: bool) -> AVResult<()> {
+    unsafe {
+        if update || ctx.oformat.is_null() || ctx.url.is_null() {
+            return Ok(());
+        }
+        if CStr::from_ptr((*ctx.oformat).name).to_bytes() != b"image2" {
+            return Ok(());
+        }
+        if av_filename_number_test(ctx.url) == 0 {
+            let url = CStr::from_ptr(ctx.url).to_string_lossy();
+            return Err(
+                format!("The image2 output requires a pattern like `%05d`: {}", url).into(),
+            );
+        }
+        Ok(())
+    }
+}
+
 /// Audio Description
+///
+/// Create with `AudioDesc::new()` or the `with_*()` constructors and set the
+/// fields after, more fields may be added without a major version.
+///
+/// The writers create an audio stream for the description with a codec, the
+/// audio descriptions were skipped before and didn't take a `stream_index`.
+/// Use `AudioDesc::new()` to keep a placeholder without a stream.
 #[derive(Copy, Clone, Debug, Default)]
+#[non_exhaustive]
 pub struct AudioDesc {
     pub codec_id: AVCodecID,
     pub sample_fmt: AVSampleFormat,
     pub bit_rate: i64,
     pub sample_rate: usize,
     pub channels: usize,
+    /// Channel layout (`AV_CH_LAYOUT_*`), the default layout of the `channels`
+    /// is used if zero.
+    pub channel_layout: u64,
+    /// Time base of the timestamps, `1/sample_rate` is used if not specified.
+    pub time_base: AVRational,
 }
 
 impl MediaDesc for AudioDesc {
     fn codec_id(&self) -> AVCodecID {
         self.codec_id
     }
+    fn as_audio_desc(&self) -> Option<&AudioDesc> {
+        Some(self)
+    }
 }
 
 impl AudioDesc {
     pub fn new() -> Self {
         Default::default()
     }
+
+    pub fn with_aac(sample_rate: usize, channels: usize, bit_rate: i64) -> Self {
+        Self::with_codec(AV_CODEC_ID_AAC, sample_rate, channels, bit_rate)
+    }
+
+    /// The `sample_rate` of Opus should be 48000, other rates are resampled
+    /// by the decoders.
+    pub fn with_opus(sample_rate: usize, channels: usize, bit_rate: i64) -> Self {
+        Self::with_codec(AV_CODEC_ID_OPUS, sample_rate, channels, bit_rate)
+    }
+
+    /// Create an audio description with the planar float samples.
+    fn with_codec(codec_id: AVCodecID, sample_rate: usize, channels: usize, bit_rate: i64) -> Self {
+        let channel_layout = unsafe { av_get_default_channel_layout(channels as i32) as u64 };
+        Self {
+            codec_id,
+            sample_fmt: AVSampleFormat::AV_SAMPLE_FMT_FLTP,
+            bit_rate,
+            sample_rate,
+            channels,
+            channel_layout,
+            ..Default::default()
+        }
+    }
 }
 
 /// Video Description
@@ -114,6 +463,23 @@ pub struct VideoDesc {
     pub time_base: AVRational,
     pub gop_size: i32,
     pub pix_fmt: AVPixelFormat,
+    /// Sample aspect ratio, `0/1` if unknown.
+    pub sample_aspect_ratio: AVRational,
+    /// Rotation angle in degrees (counterclockwise) of the display matrix.
+    pub rotation: Option<f64>,
+    /// Time base of the output stream, the muxer chooses if not specified.
+    ///
+    /// It's a hint, the muxer may still override it on writing the header,
+    /// like: `mpegts` always uses `1/90000`. The timestamps are rescaled from
+    /// the `time_base` to the final one, see `SimpleWriter::stream_time_base()`.
+    pub output_time_base: Option<AVRational>,
+    /// Name of the encoder, like: `h264_nvenc`, the default encoder of the
+    /// codec if not specified. It must be compiled into the linked FFmpeg and
+    /// encode the `codec_id`.
+    pub encoder_name: Option<&'static str>,
+    /// Options of the encoder, like: `preset=fast:crf=23:tune=zerolatency`
+    /// of `libx264`, see `new_encoder()`. Not used for muxing.
+    pub codec_options: Option<&'static str>,
 }
 
 impl MediaDesc for VideoDesc {
@@ -136,9 +502,10 @@ impl VideoDesc {
             width,
             height,
             bit_rate,
-            time_base: AVRational::with_normalize(time_unit),
+            time_base: time_base_of_unit(time_unit),
             gop_size: 12,
             pix_fmt: AV_PIX_FMT_YUV420P,
+            ..Default::default()
         }
     }
 
@@ -148,9 +515,238 @@ impl VideoDesc {
             width,
             height,
             bit_rate,
-            time_base: AVRational::with_normalize(time_unit),
+            time_base: time_base_of_unit(time_unit),
+            gop_size: 12,
+            pix_fmt: AV_PIX_FMT_YUV420P,
+            ..Default::default()
+        }
+    }
+
+    /// Create a VP9 video description, like: for the `webm` format.
+    pub fn with_vp9(width: i32, height: i32, bit_rate: i64, time_unit: i32) -> Self {
+        Self {
+            codec_id: AV_CODEC_ID_VP9,
+            width,
+            height,
+            bit_rate,
+            time_base: time_base_of_unit(time_unit),
             gop_size: 12,
             pix_fmt: AV_PIX_FMT_YUV420P,
+            ..Default::default()
+        }
+    }
+
+    /// Create a raw video description, like: for the `nut`, `matroska` or
+    /// `rawvideo` format.
+    ///
+    /// Each frame written is the packed planes of the `pix_fmt`, without
+    /// padding. The `frame_rate` is used as the output time base. Not for the
+    /// `yuv4mpegpipe` (`.y4m`), it only accepts the `WRAPPED_AVFRAME` of the
+    /// encoders in FFmpeg 4.3.
+    pub fn with_rawvideo(
+        width: i32,
+        height: i32,
+        pix_fmt: AVPixelFormat,
+        frame_rate: AVRational,
+        time_unit: i32,
+    ) -> Self {
+        Self {
+            codec_id: AV_CODEC_ID_RAWVIDEO,
+            width,
+            height,
+            time_base: time_base_of_unit(time_unit),
+            pix_fmt,
+            output_time_base: Some(AVRational::new(frame_rate.den, frame_rate.num)),
+            ..Default::default()
+        }
+    }
+
+    /// Create and open an encoder of the description.
+    ///
+    /// The encoder is chosen by `encoder_name` or `codec_id`, and opened
+    /// with the `codec_options`, returns error if any of them is not
+    /// recognized by the encoder.
+    pub fn new_encoder(&self) -> AVResult<AVCodecContextOwned> {
+        let mut encoder = match self.encoder_name {
+            Some(name) => AVCodecContextOwned::with_encoder_name(name)?,
+            None => AVCodecContextOwned::with_encoder(self.codec_id)?,
+        };
+        encoder.width = self.width;
+        encoder.height = self.height;
+        encoder.bit_rate = self.bit_rate;
+        encoder.time_base = self.time_base;
+        encoder.gop_size = self.gop_size;
+        encoder.pix_fmt = self.pix_fmt;
+        encoder.sample_aspect_ratio = self.sample_aspect_ratio;
+        encoder.open(self.codec_options)?;
+        Ok(encoder)
+    }
+
+    /// Create a video description from an input stream, for remuxing.
+    ///
+    /// The sample aspect ratio and the rotation of the display matrix are
+    /// captured, the `time_base` is the stream's time base. The extradata is
+    /// not, set it by `SimpleWriter::set_extradata()`, or copy all the codec
+    /// parameters by `SimpleWriter::add_stream_from()`.
+    pub fn from_stream(stream: &AVStream) -> Self {
+        let mut desc = Self {
+            time_base: stream.time_base,
+            rotation: stream.rotation_degrees(),
+            ..Default::default()
+        };
+        if let Some(par) = stream.codecpar() {
+            if par.codec_type != AVMEDIA_TYPE_VIDEO {
+                return desc;
+            }
+            desc.codec_id = par.codec_id;
+            desc.width = par.width;
+            desc.height = par.height;
+            desc.bit_rate = par.bit_rate;
+            desc.pix_fmt = pix_fmt_from_raw(par.format).unwrap_or(AV_PIX_FMT_NONE);
+            desc.sample_aspect_ratio = par.sample_aspect_ratio;
+        }
+        desc
+    }
+}
+
+/// The default `TextSampleEntry` of `mov_text`, required by the mp4 muxer.
+const MOV_TEXT_SAMPLE_ENTRY: [u8; 48] = [
+    0x00, 0x00, 0x00, 0x00, // displayFlags
+    0x01, // horizontal-justification
+    0xFF, // vertical-justification
+    0x00, 0x00, 0x00, 0x00, // background-color-rgba
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // BoxRecord
+    0x00, 0x00, // StyleRecord: startChar
+    0x00, 0x00, // StyleRecord: endChar
+    0x00, 0x01, // StyleRecord: font-ID
+    0x00, // StyleRecord: face-style-flags
+    0x12, // StyleRecord: font-size
+    0xFF, 0xFF, 0xFF, 0xFF, // StyleRecord: text-color-rgba
+    0x00, 0x00, 0x00, 0x12, b'f', b't', b'a', b'b', // FontTableBox
+    0x00, 0x01, // FontTableBox: entry-count
+    0x00, 0x01, 0x05, b'S', b'e', b'r', b'i', b'f', // FontRecord
+];
+
+/// Subtitle Description
+#[derive(Clone, Debug, Default)]
+pub struct SubtitleDesc {
+    pub codec_id: AVCodecID,
+    pub time_base: AVRational,
+    /// Out-of-band global headers, like: TextSampleEntry of mov_text.
+    pub extradata: Vec<u8>,
+}
+
+impl MediaDesc for SubtitleDesc {
+    fn codec_id(&self) -> AVCodecID {
+        self.codec_id
+    }
+    fn as_subtitle_desc(&self) -> Option<&SubtitleDesc> {
+        Some(self)
+    }
+}
+
+impl SubtitleDesc {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Timed text for mp4, with the default text sample entry.
+    pub fn with_mov_text(time_unit: i32) -> Self {
+        Self {
+            codec_id: AV_CODEC_ID_MOV_TEXT,
+            time_base: time_base_of_unit(time_unit),
+            extradata: MOV_TEXT_SAMPLE_ENTRY.to_vec(),
+        }
+    }
+
+    /// WebVTT for webm, mkv or mp4.
+    pub fn with_webvtt(time_unit: i32) -> Self {
+        Self {
+            codec_id: AV_CODEC_ID_WEBVTT,
+            time_base: time_base_of_unit(time_unit),
+            extradata: vec![],
+        }
+    }
+
+    /// Encode the plain text to a `mov_text` sample, the 16-bit length prefixed UTF-8.
+    pub fn encode_mov_text(text: &str) -> Vec<u8> {
+        let len = text.len().min(u16::MAX as usize);
+        let mut bytes = Vec::with_capacity(len + 2);
+        bytes.extend_from_slice(&(len as u16).to_be_bytes());
+        bytes.extend_from_slice(&text.as_bytes()[..len]);
+        bytes
+    }
+}
+
+/// Data Description, like: timed metadata.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DataDesc {
+    pub codec_id: AVCodecID,
+    pub time_base: AVRational,
+}
+
+impl MediaDesc for DataDesc {
+    fn codec_id(&self) -> AVCodecID {
+        self.codec_id
+    }
+    fn as_data_desc(&self) -> Option<&DataDesc> {
+        Some(self)
+    }
+}
+
+impl DataDesc {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Timed ID3 metadata for mpegts, like: the ad markers of HLS.
+    ///
+    /// Each frame is a complete ID3v2 tag.
+    pub fn with_timed_id3(time_unit: i32) -> Self {
+        Self {
+            codec_id: AV_CODEC_ID_TIMED_ID3,
+            time_base: time_base_of_unit(time_unit),
+        }
+    }
+
+    /// SMPTE 336M KLV metadata for mpegts.
+    pub fn with_klv(time_unit: i32) -> Self {
+        Self {
+            codec_id: AV_CODEC_ID_SMPTE_KLV,
+            time_base: time_base_of_unit(time_unit),
+        }
+    }
+}
+
+/// Statistics of the muxing throughput.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WriterStats {
+    /// Number of the packets written.
+    pub packets: u64,
+    /// Number of the payload bytes written.
+    pub bytes: u64,
+    /// Time spent in writing the packets to the muxer.
+    pub write_time: Duration,
+}
+
+impl WriterStats {
+    /// Returns the packets written per second of the `write_time`.
+    pub fn packets_per_sec(&self) -> f64 {
+        let secs = self.write_time.as_secs_f64();
+        if secs > 0.0 {
+            self.packets as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the bytes written per second of the `write_time`.
+    pub fn bytes_per_sec(&self) -> f64 {
+        let secs = self.write_time.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
         }
     }
 }
@@ -160,16 +756,95 @@ impl VideoDesc {
 pub struct Stream {
     stream: AVStreamOwned,
     in_time_base: AVRational,
+    /// Offset added to the timestamps (in ns).
+    offset: i64,
+    /// The dts of the last written frame, in the output time base.
+    last_dts: Option<i64>,
+    /// Size of each frame in bytes of the raw video.
+    frame_size: Option<usize>,
+}
+
+/// The non-seekable sink of the faststart output, the muxer writes to the
+/// temporary file, which is copied to the sink after the trailer written.
+struct FaststartSink {
+    path: PathBuf,
+    sink: Box<dyn std::io::Write + Send>,
+}
+
+impl Drop for FaststartSink {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log_warn!("Failed to remove {:?}: {}", self.path, err);
+        }
+    }
+}
+
+impl FaststartSink {
+    /// Copy the rearranged file to the sink.
+    fn copy(&mut self) -> AVResult<()> {
+        let mut file = std::fs::File::open(&self.path)?;
+        std::io::copy(&mut file, &mut self.sink)?;
+        std::io::Write::flush(&mut self.sink)?;
+        Ok(())
+    }
 }
 
+/// The Callback for inspecting the packets before muxing.
+/// # Arguments
+/// * `packet` - The packet with the timestamps rescaled to the stream time base.
+pub type PacketInspector = dyn Fn(&AVPacket) + Send + Sync;
+
 /// Simple Writer for Muxing Audio and Video.
-#[derive(Debug)]
 pub struct SimpleWriter {
     ctx: AVFormatContextOwned,
     format_options: String,
     streams: Vec<Stream>,
     header_writed: bool,
     trailer_writed: bool,
+    /// The attached picture to write after the header, with the stream index.
+    cover_art: Option<(usize, Vec<u8>)>,
+    stats: WriterStats,
+    /// Bump the non-increasing dts of the frames.
+    fix_dts: bool,
+    /// Write the packets by `av_interleaved_write_frame()`.
+    interleaved: bool,
+    /// Flush the I/O context after each packet.
+    auto_flush: bool,
+    /// The buffer of the output if muxing into the memory.
+    memory: Option<SharedBuffer>,
+    /// The checksum of the output file, with the path.
+    checksum: Option<(Checksum, PathBuf)>,
+    /// Callback on each packet before muxing.
+    on_packet: Option<Arc<PacketInspector>>,
+    /// The sink of the faststart output, dropped after the `ctx` closed.
+    faststart_sink: Option<FaststartSink>,
+    /// The `stream_index` of each media description.
+    media_streams: Vec<Option<usize>>,
+}
+
+impl Debug for SimpleWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimpleWriter")
+            .field("ctx", &self.ctx)
+            .field("format_options", &self.format_options)
+            .field("streams", &self.streams)
+            .field("header_writed", &self.header_writed)
+            .field("trailer_writed", &self.trailer_writed)
+            .field("cover_art", &self.cover_art)
+            .field("stats", &self.stats)
+            .field("fix_dts", &self.fix_dts)
+            .field("interleaved", &self.interleaved)
+            .field("auto_flush", &self.auto_flush)
+            .field("memory", &self.memory)
+            .field("checksum", &self.checksum)
+            .field("on_packet", &self.on_packet.is_some())
+            .field("media_streams", &self.media_streams)
+            .field(
+                "faststart_sink",
+                &self.faststart_sink.as_ref().map(|x| &x.path),
+            )
+            .finish()
+    }
 }
 
 impl Drop for SimpleWriter {
@@ -199,48 +874,59 @@ impl Writer for SimpleWriter {
         is_key_frame: bool,
         stream_index: usize,
     ) -> AVResult<()> {
-        if !self.header_writed {
-            self.ctx.write_header(Some(&self.format_options))?;
-            self.header_writed = true;
-        }
-        unsafe {
-            let stm = self.streams.get(stream_index).unwrap();
-            let in_time_base = stm.in_time_base;
-            let out_time_base = stm.stream.time_base;
-            let mut pkt = AVPacket::default();
-            let pts = av_rescale_q_rnd(
-                pts,
-                in_time_base,
-                out_time_base,
-                AVRounding::new().near_inf().pass_min_max(),
-            );
-            pkt.pts = pts;
-            pkt.dts = pts;
-            pkt.data = bytes.as_ptr() as *mut u8;
-            pkt.size = bytes.len().try_into()?;
-            pkt.stream_index = stream_index.try_into()?;
-            pkt.flags = if is_key_frame { AV_PKT_FLAG_KEY } else { 0 };
-            pkt.duration = av_rescale_q(duration, in_time_base, out_time_base);
-            pkt.pos = -1;
-            self.ctx.write_frame_interleaved(&mut pkt)?;
-            self.ctx.flush();
-            Ok(())
-        }
+        let mut pkt = AVBytesPacket::new(bytes, AV_NOPTS_VALUE, AV_NOPTS_VALUE, 0, 0, 0)?;
+        self.write_packet(&mut pkt, pts, pts, duration, is_key_frame, stream_index)
+    }
+
+    /// Write frame bytes with the decompression timestamp to the stream.
+    fn write_bytes_with_dts(
+        &mut self,
+        bytes: &[u8],
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        let mut pkt = AVBytesPacket::new(bytes, AV_NOPTS_VALUE, AV_NOPTS_VALUE, 0, 0, 0)?;
+        self.write_packet(&mut pkt, pts, dts, duration, is_key_frame, stream_index)
+    }
+
+    /// Write the reference counted frame bytes to the stream without copying.
+    #[cfg(feature = "bytes")]
+    fn write_bytes_ref(
+        &mut self,
+        data: Bytes,
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        let mut pkt = AVPacketOwned::from_bytes(data)?;
+        self.write_packet(&mut pkt, pts, pts, duration, is_key_frame, stream_index)
     }
 
     /// Write the trailer to finish the muxing.
     fn write_trailer(&mut self) -> AVResult<()> {
         if self.header_writed && !self.trailer_writed {
-            self.ctx.write_trailer()?;
+            // The trailer must not be written twice even if failed.
             self.trailer_writed = true;
+            self.ctx.write_trailer()?;
             self.flush();
+            if let Some(faststart_sink) = self.faststart_sink.as_mut() {
+                faststart_sink.copy()?;
+            }
         }
         Ok(())
     }
 
     /// Close all resouces accessed by the muxer.
+    ///
+    /// The error of writing the trailer is ignored, use `finish()` to check it.
     fn close(&mut self) {
-        self.write_trailer().unwrap();
+        if let Err(err) = self.write_trailer() {
+            log_warn!("Failed to write the trailer: {}", err);
+        }
         self.ctx.flush();
     }
 
@@ -250,13 +936,121 @@ impl Writer for SimpleWriter {
     }
 
     /// Returns the size of the stream processed.
+    ///
+    /// The bytes of the packets are counted if the muxer writes the files by
+    /// itself, like: `image2`.
     fn size(&self) -> u64 {
-        self.ctx.size()
+        match self.ctx.size() {
+            0 => self.stats.bytes,
+            size => size,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
 
 impl SimpleWriter {
+    /// Rescale the timestamps and write the packet to the stream.
+    fn write_packet(
+        &mut self,
+        pkt: &mut AVPacket,
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        // Checked before writing the header, the invalid frame writes nothing.
+        let frame_size = self
+            .streams
+            .get(stream_index)
+            .ok_or_else(|| format!("Invalid stream index: {}", stream_index))?
+            .frame_size;
+        if let Some(frame_size) = frame_size {
+            if pkt.size as usize != frame_size {
+                return Err(format!(
+                    "The raw video frame must be {} bytes, got {}",
+                    frame_size, pkt.size
+                )
+                .into());
+            }
+        }
+        if !self.header_writed {
+            self.ctx.write_header(Some(&self.format_options))?;
+            self.header_writed = true;
+            if let Some((index, bytes)) = self.cover_art.take() {
+                // The attached picture must have pts 0 for mp4.
+                let mut pkt = AVBytesPacket::new(&bytes, 0, 0, 0, AV_PKT_FLAG_KEY, index)?;
+                self.ctx.write_frame(&mut pkt)?;
+            }
+        }
+        let stm = &mut self.streams[stream_index];
+        let in_time_base = stm.in_time_base;
+        let out_time_base = stm.stream.time_base;
+        let offset = rescale_ts(stm.offset, NANOS_TIME_BASE, out_time_base);
+        pkt.pts = pts;
+        pkt.dts = dts;
+        pkt.duration = duration;
+        rescale_packet(pkt, in_time_base, out_time_base);
+        // Avoid the sub time base duration rounded to 0, which means unknown.
+        if pkt.duration == 0 && duration > 0 {
+            pkt.duration = 1;
+        }
+        let add_offset = |ts: i64| match ts {
+            AV_NOPTS_VALUE => AV_NOPTS_VALUE,
+            ts => ts + offset,
+        };
+        let mut pts = add_offset(pkt.pts);
+        let mut dts = add_offset(pkt.dts);
+        if self.fix_dts && dts != AV_NOPTS_VALUE {
+            if let Some(last_dts) = stm.last_dts {
+                if dts <= last_dts {
+                    dts = last_dts + 1;
+                }
+            }
+            stm.last_dts = Some(dts);
+            // The frame can not be presented before decoded.
+            if pts != AV_NOPTS_VALUE && pts < dts {
+                pts = dts;
+            }
+        }
+        pkt.pts = pts;
+        pkt.dts = dts;
+        // Each frame of the raw video is a key frame.
+        let is_key_frame = is_key_frame || stm.frame_size.is_some();
+        pkt.flags = if is_key_frame { AV_PKT_FLAG_KEY } else { 0 };
+        // The index in the format context differs if extra streams were added.
+        pkt.stream_index = stm.stream.index;
+        if let Some(ref on_packet) = self.on_packet {
+            on_packet(pkt);
+        }
+        let size = pkt.size.max(0) as u64;
+        let start = Instant::now();
+        if self.interleaved {
+            self.ctx.write_frame_interleaved(pkt)?;
+        } else {
+            self.ctx.write_frame(pkt)?;
+        }
+        if self.auto_flush {
+            self.ctx.flush();
+        }
+        self.stats.packets += 1;
+        self.stats.bytes += size;
+        self.stats.write_time += start.elapsed();
+        Ok(())
+    }
+
     /// Create a new simple writer.
+    ///
+    /// For the image sequences (the `image2` format), each frame is written
+    /// to a file, the `path` must contain a printf pattern of the frame
+    /// number, like: `/tmp/frame_%05d.png`.
     /// # Arguments
     /// * `path` - Path of the output file.
     /// * `descs` - Media description of input streams.
@@ -271,32 +1065,256 @@ impl SimpleWriter {
     where
         P: AsRef<Path> + Sized,
     {
-        let mut ctx = AVFormatContextOwned::with_output(path, format, None)?;
+        check_medias(descs, format_options)?;
+        let ctx = AVFormatContextOwned::with_output(path, format, None)?;
+        Self::with_context(ctx, descs, format_options)
+    }
+
+    /// Create a new simple writer muxes into an internal growable buffer,
+    /// the bytes are retrieved by `into_bytes()`.
+    ///
+    /// The `format` must be specified, like: `mpegts` or `mp4`.
+    pub fn new_in_memory(
+        descs: &[&dyn MediaDesc],
+        format: &str,
+        format_options: Option<&str>,
+    ) -> AVResult<Self> {
+        let memory = SharedBuffer::new();
+        let io = AVIOContextOwned::with_writer(memory.clone())?;
+        let ctx = AVFormatContextOwned::with_output_io("", Some(format), io)?;
+        let mut writer = Self::with_context(ctx, descs, format_options)?;
+        writer.memory = Some(memory);
+        Ok(writer)
+    }
+
+    /// Write the trailer and returns the bytes muxed by the writer created
+    /// with `new_in_memory()`.
+    pub fn into_bytes(mut self) -> AVResult<Vec<u8>> {
+        self.write_trailer()?;
+        self.flush();
+        match self.memory.take() {
+            Some(memory) => Ok(memory.take()),
+            None => Err("The writer is not muxing into the memory".into()),
+        }
+    }
+
+    /// Create a new simple writer computes the checksum of the output file,
+    /// retrieved by `checksum()`, for verifying the integrity of the copies.
+    ///
+    /// The custom I/O is used to compute the checksum of the bytes written.
+    pub fn with_checksum<P>(
+        path: P,
+        descs: &[&dyn MediaDesc],
+        format: Option<&str>,
+        format_options: Option<&str>,
+    ) -> AVResult<Self>
+    where
+        P: AsRef<Path> + Sized,
+    {
+        check_medias(descs, format_options)?;
+        let checksum = Checksum::new();
+        let file = std::fs::File::create(path.as_ref())?;
+        let io = AVIOContextOwned::with_writer(ChecksumWriter::new(file, checksum.clone()))?;
+        let ctx = AVFormatContextOwned::with_output_io(path.as_ref(), format, io)?;
+        let mut writer = Self::with_context(ctx, descs, format_options)?;
+        writer.checksum = Some((checksum, path.as_ref().to_path_buf()));
+        Ok(writer)
+    }
+
+    /// Returns the CRC-32 (IEEE 802.3, as zlib) of the output file created by
+    /// `with_checksum()`, should be called after `write_trailer()`.
+    ///
+    /// It covers the muxed bytes of the file, not the frames written. If the
+    /// muxer rewrote the bytes written before (like: mp4), the file is read
+    /// back to compute.
+    pub fn checksum(&self) -> AVResult<u64> {
+        let (checksum, path) = self
+            .checksum
+            .as_ref()
+            .ok_or("The writer is not computing the checksum")?;
+        if let Some(value) = checksum.value() {
+            return Ok(u64::from(value));
+        }
+        let mut file = std::fs::File::open(path)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut crc = 0;
+        loop {
+            let n = std::io::Read::read(&mut file, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            crc = crc32(crc, &buf[..n]);
+        }
+        Ok(u64::from(crc))
+    }
+
+    /// Create a new simple writer muxes the faststart mp4 (the `moov` atom at
+    /// the front) to a non-seekable sink, like: a pipe or a socket of the
+    /// progressive download over HTTP.
+    ///
+    /// The muxer writes to a temporary file, which is rearranged and copied
+    /// to the `sink` by `write_trailer()`, nothing is written to the `sink`
+    /// before. The `movflags` must contain `faststart`.
+    pub fn with_faststart_sink<W>(
+        sink: W,
+        descs: &[&dyn MediaDesc],
+        format: Option<&str>,
+        format_options: Option<&str>,
+    ) -> AVResult<Self>
+    where
+        W: std::io::Write + Send + 'static,
+    {
+        let options = AVDictionaryOwned::from_str(format_options.unwrap_or(""))?;
+        if !has_movflag(&options.get("movflags").unwrap_or_default(), "faststart") {
+            return Err("The movflags must contain faststart".into());
+        }
+        static SEQ: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "ffav-faststart-{}-{}.mp4",
+            std::process::id(),
+            SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+        let faststart_sink = FaststartSink {
+            path,
+            sink: Box::new(sink),
+        };
+        let ctx = AVFormatContextOwned::with_output(&faststart_sink.path, format, None)?;
+        let mut writer = Self::with_context(ctx, descs, format_options)?;
+        writer.faststart_sink = Some(faststart_sink);
+        Ok(writer)
+    }
+
+    /// Create a new simple writer appends to the existing file.
+    ///
+    /// FFmpeg can't open the file for appending, the custom I/O is used to write
+    /// after the existing data. This only makes sense for the formats can be
+    /// concatenated, like: mpegts, the formats with global header or index,
+    /// like: mp4, will produce a corrupted file.
+    pub fn append<P>(
+        path: P,
+        descs: &[&dyn MediaDesc],
+        format: Option<&str>,
+        format_options: Option<&str>,
+    ) -> AVResult<Self>
+    where
+        P: AsRef<Path> + Sized,
+    {
+        let file = AppendFile::open(path.as_ref())?;
+        let io = AVIOContextOwned::with_writer(file)?;
+        let ctx = AVFormatContextOwned::with_output_io(path, format, io)?;
+        Self::with_context(ctx, descs, format_options)
+    }
+
+    fn with_context(
+        mut ctx: AVFormatContextOwned,
+        descs: &[&dyn MediaDesc],
+        format_options: Option<&str>,
+    ) -> AVResult<Self> {
+        check_medias(descs, format_options)?;
+        let options = AVDictionaryOwned::from_str(format_options.unwrap_or(""))?;
+        let movflags = options.get("movflags").unwrap_or_default();
+        check_image_pattern(&ctx, options.get("update").as_deref() == Some("1"))?;
+        // The file is reopened by url to move the moov atom in the trailer.
+        if has_movflag(&movflags, "faststart") && (ctx.is_custom_io() || !ctx.is_seekable()) {
+            return Err(
+                "The faststart requires a seekable file output, see with_faststart_sink()".into(),
+            );
+        }
+        let media_streams = media_stream_indexes(descs.iter().copied());
         let mut streams: Vec<Stream> = vec![];
-        for desc in descs {
-            let codec_id = desc.codec_id();
-            match codec_id {
-                AV_CODEC_ID_H264 | AV_CODEC_ID_HEVC => {
-                    let desc = desc.as_video_desc().unwrap();
-                    let mut st = ctx.new_stream(codec_id)?;
-                    // st.time_base = AVRational::new(1, 90000);
-                    if let Some(par) = st.codecpar_mut() {
-                        par.codec_type = AVMEDIA_TYPE_VIDEO;
-                        par.codec_id = codec_id;
-                        par.bit_rate = desc.bit_rate;
-                        par.width = desc.width;
-                        par.height = desc.height;
-                        par.field_order = AV_FIELD_UNKNOWN;
-                        par.sample_aspect_ratio = AVRational::new(0, 1);
-                        par.profile = FF_PROFILE_UNKNOWN;
-                        par.level = FF_LEVEL_UNKNOWN;
+        // The streams are created by the order of the `stream_index`.
+        let medias = descs.iter().zip(&media_streams);
+        for (media, _) in medias.filter(|(_, index)| index.is_some()) {
+            let codec_id = media.codec_id();
+            if let Some(desc) = media.as_video_desc() {
+                // The encoder is checked to encode the codec by `check_medias()`.
+                let mut st = match desc.encoder_name {
+                    Some(name) => ctx.new_stream_with_encoder(name)?,
+                    None => ctx.new_stream(codec_id)?,
+                };
+                if let Some(output_time_base) = desc.output_time_base {
+                    st.time_base = output_time_base;
+                }
+                st.set_video_params(
+                    codec_id,
+                    desc.width,
+                    desc.height,
+                    desc.pix_fmt,
+                    desc.sample_aspect_ratio,
+                )?;
+                if let Some(par) = st.codecpar_mut() {
+                    par.bit_rate = desc.bit_rate;
+                }
+                if let Some(rotation) = desc.rotation {
+                    st.set_rotation(rotation)?;
+                }
+                let frame_size = if codec_id == AV_CODEC_ID_RAWVIDEO {
+                    Some(raw_frame_size(desc)?)
+                } else {
+                    None
+                };
+                streams.push(Stream {
+                    stream: st,
+                    in_time_base: desc.time_base,
+                    offset: 0,
+                    last_dts: None,
+                    frame_size,
+                });
+            } else if let Some(desc) = media.as_subtitle_desc() {
+                let mut st = ctx.new_stream(codec_id)?;
+                st.set_codec(AVMEDIA_TYPE_SUBTITLE, codec_id)?;
+                if !desc.extradata.is_empty() {
+                    st.set_extradata(&desc.extradata)?;
+                }
+                streams.push(Stream {
+                    stream: st,
+                    in_time_base: desc.time_base,
+                    offset: 0,
+                    last_dts: None,
+                    frame_size: None,
+                });
+            } else if let Some(desc) = media.as_data_desc() {
+                let mut st = ctx.new_stream(codec_id)?;
+                st.set_codec(AVMEDIA_TYPE_DATA, codec_id)?;
+                streams.push(Stream {
+                    stream: st,
+                    in_time_base: desc.time_base,
+                    offset: 0,
+                    last_dts: None,
+                    frame_size: None,
+                });
+            } else if let Some(desc) = media.as_audio_desc() {
+                let time_base = if desc.time_base.den > 0 {
+                    desc.time_base
+                } else {
+                    AVRational::new(1, desc.sample_rate.try_into()?)
+                };
+                let mut st = ctx.new_stream(codec_id)?;
+                st.set_audio_params(
+                    codec_id,
+                    desc.sample_rate.try_into()?,
+                    desc.channels.try_into()?,
+                    desc.sample_fmt,
+                )?;
+                if let Some(par) = st.codecpar_mut() {
+                    par.bit_rate = desc.bit_rate;
+                    if desc.channel_layout != 0 {
+                        par.channel_layout = desc.channel_layout;
                     }
-                    streams.push(Stream {
-                        stream: st,
-                        in_time_base: desc.time_base,
-                    });
                 }
-                _ => {}
+                streams.push(Stream {
+                    stream: st,
+                    in_time_base: time_base,
+                    offset: 0,
+                    last_dts: None,
+                    frame_size: None,
+                });
+            } else {
+                return Err(format!(
+                    "The media of {:?} is neither video, audio, subtitle nor data",
+                    codec_id
+                )
+                .into());
             }
         }
         Ok(Self {
@@ -305,8 +1323,219 @@ impl SimpleWriter {
             streams,
             header_writed: false,
             trailer_writed: false,
+            cover_art: None,
+            stats: Default::default(),
+            fix_dts: false,
+            interleaved: true,
+            auto_flush: true,
+            memory: None,
+            checksum: None,
+            on_packet: None,
+            faststart_sink: None,
+            media_streams,
         })
     }
+
+    /// Close the current fragment of the fragmented mp4 and start a new one
+    /// in the same file, like: the chunks of the CMAF segments for LL-HLS.
+    ///
+    /// The `movflags` must contain `frag_custom`, like: `movflags=cmaf+frag_custom`,
+    /// so the fragments (`moof` + `mdat`) are cut only by this call. Unlike
+    /// the `SplitWriter` rotates the files, the fragments share the `moov` at
+    /// the start of the file, each fragment should start with a key frame to
+    /// be decodable on its own.
+    ///
+    /// Does nothing if no frame written.
+    pub fn flush_fragment(&mut self) -> AVResult<()> {
+        let options = AVDictionaryOwned::from_str(&self.format_options)?;
+        let movflags = options.get("movflags").unwrap_or_default();
+        if !has_movflag(&movflags, "frag_custom") {
+            return Err("The flush_fragment requires movflags=frag_custom".into());
+        }
+        if !self.header_writed || self.trailer_writed {
+            return Ok(());
+        }
+        self.ctx.flush_muxer()?;
+        self.ctx.flush();
+        Ok(())
+    }
+
+    /// Replace the extradata (out-of-band global headers) of the stream.
+    ///
+    /// The encoders, like: libx264, produce the SPS/PPS in the extradata only
+    /// after opened, capture it by `AVCodecContextOwned::extradata()`.
+    ///
+    /// # Ordering
+    /// The header is written with the first frame, the extradata must be set
+    /// before writing any frames, otherwise an error returned. The mp4 playback
+    /// breaks if the header is written with stale or empty extradata.
+    pub fn set_extradata(&mut self, stream_index: usize, extradata: &[u8]) -> AVResult<()> {
+        if self.header_writed {
+            return Err("The extradata must be set before writing frames".into());
+        }
+        let stm = self
+            .streams
+            .get_mut(stream_index)
+            .ok_or_else(|| format!("Invalid stream index: {}", stream_index))?;
+        stm.stream.set_extradata(extradata)
+    }
+
+    /// Embed a picture as the cover art of the output file.
+    ///
+    /// The `mime` should be `image/jpeg` or `image/png`, an attached picture
+    /// stream is created and the picture is written after the header.
+    /// The size of the stream is parsed from the picture, required by the
+    /// muxers. Must be called before writing any frames.
+    pub fn set_cover_art(&mut self, bytes: &[u8], mime: &str) -> AVResult<()> {
+        if self.header_writed {
+            return Err("The cover art must be set before writing frames".into());
+        }
+        let codec_id = match mime {
+            "image/jpeg" | "image/jpg" => AV_CODEC_ID_MJPEG,
+            "image/png" => AV_CODEC_ID_PNG,
+            _ => return Err(format!("Unsupported cover art type: {}", mime).into()),
+        };
+        let (width, height) =
+            picture_size(bytes, codec_id).ok_or("The size of the cover art not found")?;
+        let mut st = self.ctx.new_stream(codec_id)?;
+        st.disposition |= AV_DISPOSITION_ATTACHED_PIC;
+        st.set_video_params(
+            codec_id,
+            width,
+            height,
+            AV_PIX_FMT_NONE,
+            AVRational::new(0, 1),
+        )?;
+        self.cover_art = Some((st.index as usize, bytes.to_vec()));
+        Ok(())
+    }
+
+    /// Embed the SMPTE timecode of the first frame, like: `01:00:00:00`.
+    ///
+    /// The timecode is set to the metadata of the first video stream, the mp4
+    /// or mov muxer writes it as a timecode (tmcd) track. The `;` before the
+    /// frames is for the drop frame timecode, like: `01:00:00;00`.
+    /// Must be called before writing any frames.
+    pub fn set_timecode(&mut self, timecode: &str, frame_rate: AVRational) -> AVResult<()> {
+        if self.header_writed {
+            return Err("The timecode must be set before writing frames".into());
+        }
+        check_timecode(timecode, frame_rate)?;
+        let stream = self
+            .streams
+            .iter_mut()
+            .find(|x| match x.stream.codecpar() {
+                Some(par) => par.codec_type == AVMEDIA_TYPE_VIDEO,
+                None => false,
+            })
+            .ok_or("The timecode requires a video stream")?;
+        // The timecode track uses the frame rate of the video stream.
+        if stream.stream.avg_frame_rate.num <= 0 {
+            stream.stream.avg_frame_rate = frame_rate;
+        }
+        stream.stream.set_metadata("timecode", timecode)
+    }
+
+    /// Add a stream with the codec parameters copied from an input stream,
+    /// the frames are remuxed without re-encoding, like `-c copy` of ffmpeg.
+    ///
+    /// The timestamps of the frames to write are in the time base of `src`.
+    /// Returns the index of the stream for `write_bytes()`.
+    pub fn add_stream_from(&mut self, src: &AVStream) -> AVResult<usize> {
+        if self.header_writed {
+            return Err("The stream must be added before writing frames".into());
+        }
+        check_time_base(src.time_base)?;
+        let codec_id = src.codecpar().map(|x| x.codec_id).unwrap_or_default();
+        let mut st = self.ctx.new_stream(codec_id)?;
+        st.copy_parameters_from(src)?;
+        st.sample_aspect_ratio = src.sample_aspect_ratio;
+        if let Some(par) = st.codecpar_mut() {
+            // The codec tag of the input may be invalid for the output format.
+            par.codec_tag = 0;
+        }
+        self.streams.push(Stream {
+            stream: st,
+            in_time_base: src.time_base,
+            offset: 0,
+            last_dts: None,
+            frame_size: None,
+        });
+        Ok(self.streams.len() - 1)
+    }
+
+    /// Returns the number of the streams to write, the `stream_index` of the
+    /// frames must be less than it.
+    pub fn stream_count(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns the `stream_index` to write the frames of the media by its
+    /// index in the `descs`, `None` for the medias without stream, like:
+    /// `AudioDesc::new()`, which shift the index of the following medias.
+    ///
+    /// The `stream_index` is mapped to the index of the `AVStream` in the
+    /// output internally, the muxer may add the streams, like: `hls`.
+    pub fn output_stream_index(&self, desc_index: usize) -> Option<usize> {
+        self.media_streams.get(desc_index).copied().flatten()
+    }
+
+    /// Returns the time base of the output stream.
+    ///
+    /// The muxer may change it on writing the header, it's final after the
+    /// first frame written.
+    pub fn stream_time_base(&self, stream_index: usize) -> Option<AVRational> {
+        self.streams.get(stream_index).map(|x| x.stream.time_base)
+    }
+
+    /// Returns the statistics of the muxing throughput.
+    pub fn stats(&self) -> WriterStats {
+        self.stats
+    }
+
+    /// Bump the dts of the frame to `last_dts + 1` if not increasing, instead
+    /// of failing with "non monotonically increasing dts" by the muxer.
+    ///
+    /// For the sources deliver the out-of-order or duplicate timestamps.
+    pub fn set_fix_dts(&mut self, fix_dts: bool) {
+        self.fix_dts = fix_dts;
+    }
+
+    /// Write the packets by `av_interleaved_write_frame()`, the muxer buffers
+    /// and orders them by dts across the streams, `true` by default.
+    ///
+    /// The frames must be written in the dts order of all the streams if not.
+    pub fn set_interleaved(&mut self, interleaved: bool) {
+        self.interleaved = interleaved;
+    }
+
+    /// Flush the I/O context after each packet, `true` by default.
+    ///
+    /// The output is written in larger chunks if not, at the cost of latency.
+    pub fn set_auto_flush(&mut self, auto_flush: bool) {
+        self.auto_flush = auto_flush;
+    }
+
+    /// Set the callback invoked with each packet right before muxing.
+    ///
+    /// For debugging the timestamps, the pts, dts and duration of the packet
+    /// are rescaled to the stream time base.
+    pub fn set_on_packet(&mut self, on_packet: Option<Arc<PacketInspector>>) {
+        self.on_packet = on_packet;
+    }
+
+    /// Apply a constant offset to the timestamps of the stream.
+    ///
+    /// The `offset_ns` (in ns, may be negative) is added to the pts after rescaling,
+    /// for example: to sync the streams with different clock origins.
+    pub fn set_stream_offset(&mut self, stream_index: usize, offset_ns: i64) -> AVResult<()> {
+        let stm = self
+            .streams
+            .get_mut(stream_index)
+            .ok_or_else(|| format!("Invalid stream index: {}", stream_index))?;
+        stm.offset = offset_ns;
+        Ok(())
+    }
 }
 
 /// The Callback for returns the the fragment file name.
@@ -317,7 +1546,8 @@ pub type FormatLocationCallback = dyn Fn(usize) -> String;
 /// The Callback for before and after split fragment.
 /// # Arguments
 /// * `index` - Current Fragment Index.
-pub type SplitNotifier = dyn Fn(usize);
+/// * `path` - Location of the fragment to be closed or just closed.
+pub type SplitNotifier = dyn Fn(usize, &Path);
 
 /// Options for SplitWriter.
 #[derive(Default)]
@@ -332,6 +1562,15 @@ pub struct SplitOptions {
     max_overhead: Option<f32>,
     split_at_keyframe: Option<bool>,
     start_index: Option<usize>,
+    resume: Option<bool>,
+    stream_offsets: Vec<(usize, i64)>,
+    cover_art: Option<(Vec<u8>, String)>,
+    timecode: Option<(String, AVRational)>,
+    segment_extension: Option<String>,
+    append: Option<bool>,
+    fix_dts: Option<bool>,
+    on_packet: Option<Arc<PacketInspector>>,
+    split_stream: Option<usize>,
 }
 
 impl Debug for SplitOptions {
@@ -344,6 +1583,11 @@ impl Debug for SplitOptions {
             .field("max_overhead", &self.max_overhead)
             .field("split_at_keyframe", &self.split_at_keyframe)
             .field("start_index", &self.start_index)
+            .field("resume", &self.resume)
+            .field("stream_offsets", &self.stream_offsets)
+            .field("segment_extension", &self.segment_extension)
+            .field("append", &self.append)
+            .field("split_stream", &self.split_stream)
             .finish()
     }
 }
@@ -352,6 +1596,8 @@ impl Debug for SplitOptions {
 pub struct SplitWriter {
     /// Media descriptions.
     medias: Vec<Box<dyn MediaDesc>>,
+    /// Codec of the streams, by the `stream_index` of the frames.
+    stream_codecs: Vec<AVCodecID>,
     /// The format to muxing.
     format: Option<String>,
     /// The options of muxing format.
@@ -381,6 +1627,28 @@ pub struct SplitWriter {
     start_index: usize,
     /// Current value of fragment index.
     current_index: usize,
+    /// Offsets of the stream timestamps (in ns).
+    stream_offsets: Vec<(usize, i64)>,
+    /// The cover art embedded in each fragment.
+    cover_art: Option<(Vec<u8>, String)>,
+    /// The timecode embedded in each fragment.
+    timecode: Option<(String, AVRational)>,
+    /// The extension of the fragment files, override the format derived one.
+    segment_extension: Option<String>,
+    /// Append to the existing fragment files.
+    append: bool,
+    /// Bump the non-increasing dts of the frames.
+    fix_dts: bool,
+    /// Callback on each packet before muxing.
+    on_packet: Option<Arc<PacketInspector>>,
+    /// Measure the size and time of the fragments by the stream only.
+    split_stream: Option<usize>,
+    /// Time base of the streams, by the `stream_index` of the frames.
+    stream_time_bases: Vec<AVRational>,
+    /// Bytes of the `split_stream` in the current fragment.
+    split_stream_bytes: u64,
+    /// Duration of the `split_stream` in the current fragment (in ns).
+    split_stream_time: u64,
     /// Start time of the current fragment.
     start_time: Instant,
     /// The data flow started,
@@ -388,6 +1656,20 @@ pub struct SplitWriter {
     ///
     need_key_frame: bool,
     split_wait_for_key_frame: bool,
+    /// The split requested by `force_split()`.
+    split_requested: bool,
+    /// The `max_files` changed, applied at the next split.
+    next_max_files: Option<usize>,
+    /// The `max_size_bytes` changed, applied at the next split.
+    next_max_size_bytes: Option<u64>,
+    /// The `max_size_time` changed, applied at the next split.
+    next_max_size_time: Option<u64>,
+    /// The earliest pts of the frames in the current fragment (in ns).
+    fragment_start_pts: Option<i64>,
+    /// The latest end (pts + duration) of the frames in the current fragment (in ns).
+    fragment_end_pts: Option<i64>,
+    /// Duration of the last fragment closed by split.
+    last_segment_duration: Option<Duration>,
 }
 
 impl Debug for SplitWriter {
@@ -413,30 +1695,50 @@ impl Writer for SplitWriter {
         is_key_frame: bool,
         stream_index: usize,
     ) -> AVResult<()> {
-        if self.can_split_now(is_key_frame, stream_index) {
-            self.split_now();
+        self.prepare_writer(is_key_frame, stream_index)?;
+        if let Some(ref mut writer) = self.writer {
+            writer.write_bytes(bytes, pts, duration, is_key_frame, stream_index)?;
         }
+        self.update_split_stream(bytes.len(), duration, stream_index);
+        self.update_fragment_pts(pts, duration, stream_index);
 
-        if self.writer.is_none() {
-            let writer = SimpleWriter::new(
-                self.format_location(self.current_index).to_str().unwrap(),
-                &self
-                    .medias
-                    .iter()
-                    .map(Deref::deref)
-                    .collect::<Vec<&dyn MediaDesc>>(),
-                self.format.as_deref(),
-                self.format_options.as_deref(),
-            )?;
-            self.writer = Some(Box::new(writer));
-            self.start_time = Instant::now();
-            self.started = true;
-        }
+        Ok(())
+    }
 
+    fn write_bytes_with_dts(
+        &mut self,
+        bytes: &[u8],
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.prepare_writer(is_key_frame, stream_index)?;
         if let Some(ref mut writer) = self.writer {
-            writer.write_bytes(bytes, pts, duration, is_key_frame, stream_index)?;
+            writer.write_bytes_with_dts(bytes, pts, dts, duration, is_key_frame, stream_index)?;
         }
+        self.update_split_stream(bytes.len(), duration, stream_index);
+        self.update_fragment_pts(pts, duration, stream_index);
+        Ok(())
+    }
 
+    #[cfg(feature = "bytes")]
+    fn write_bytes_ref(
+        &mut self,
+        data: Bytes,
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.prepare_writer(is_key_frame, stream_index)?;
+        let size = data.len();
+        if let Some(ref mut writer) = self.writer {
+            writer.write_bytes_ref(data, pts, duration, is_key_frame, stream_index)?;
+        }
+        self.update_split_stream(size, duration, stream_index);
+        self.update_fragment_pts(pts, duration, stream_index);
         Ok(())
     }
 
@@ -454,6 +1756,14 @@ impl Writer for SplitWriter {
         }
     }
 
+    fn finish(mut self: Box<Self>) -> AVResult<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish()
+        } else {
+            Ok(())
+        }
+    }
+
     fn flush(&mut self) {
         if let Some(writer) = &mut self.writer {
             writer.flush();
@@ -467,9 +1777,56 @@ impl Writer for SplitWriter {
             0
         }
     }
+
+    fn force_split(&mut self) {
+        SplitWriter::force_split(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 impl SplitWriter {
+    /// Split if required and open the underly writer for the next frame.
+    fn prepare_writer(&mut self, is_key_frame: bool, stream_index: usize) -> AVResult<()> {
+        if self.can_split_now(is_key_frame, stream_index) {
+            self.split_now();
+        }
+
+        if self.writer.is_none() {
+            let path = self.format_location(self.current_index);
+            let medias: Vec<&dyn MediaDesc> = self.medias.iter().map(Deref::deref).collect();
+            let format = self.format.as_deref();
+            let format_options = self.format_options.as_deref();
+            let mut writer = if self.append {
+                SimpleWriter::append(path, &medias, format, format_options)?
+            } else {
+                SimpleWriter::new(path, &medias, format, format_options)?
+            };
+            for &(index, offset) in self.stream_offsets.iter() {
+                writer.set_stream_offset(index, offset)?;
+            }
+            if let Some((ref bytes, ref mime)) = self.cover_art {
+                writer.set_cover_art(bytes, mime)?;
+            }
+            if let Some((ref timecode, frame_rate)) = self.timecode {
+                writer.set_timecode(timecode, frame_rate)?;
+            }
+            writer.set_fix_dts(self.fix_dts);
+            writer.set_on_packet(self.on_packet.clone());
+            self.writer = Some(Box::new(writer));
+            self.start_time = Instant::now();
+            self.started = true;
+        }
+
+        Ok(())
+    }
+
     /// Create a new writer with multipart files.
     /// # Arguments
     /// * `descs` - Media description of input streams.
@@ -484,14 +1841,21 @@ impl SplitWriter {
         format_options: Option<&str>,
         split_options: SplitOptions,
     ) -> AVResult<Self> {
-        let mut need_key_frame = false;
-        for d in descs.iter() {
-            if d.codec_id().has_gop() {
-                need_key_frame = true;
-            }
-        }
-        Ok(Self {
+        // The medias without stream are skipped by the SimpleWriter.
+        let stream_codecs: Vec<AVCodecID> = descs
+            .iter()
+            .filter(|x| has_stream(x.as_ref()))
+            .map(|x| x.codec_id())
+            .collect();
+        let stream_time_bases: Vec<AVRational> = descs
+            .iter()
+            .filter(|x| has_stream(x.as_ref()))
+            .map(|x| media_time_base(x.as_ref()))
+            .collect();
+        let need_key_frame = stream_codecs.iter().any(|x| codec_has_gop(*x));
+        let mut writer = Self {
             medias: descs,
+            stream_codecs,
             format: format.map(String::from),
             format_options: format_options.map(String::from),
             writer: None,
@@ -506,47 +1870,125 @@ impl SplitWriter {
             split_at_keyframe: split_options.split_at_keyframe.unwrap_or(true),
             start_index: split_options.start_index.unwrap_or(0),
             current_index: split_options.start_index.unwrap_or(0),
+            stream_offsets: split_options.stream_offsets,
+            cover_art: split_options.cover_art,
+            timecode: split_options.timecode,
+            segment_extension: split_options.segment_extension,
+            append: split_options.append.unwrap_or(false),
+            fix_dts: split_options.fix_dts.unwrap_or(false),
+            on_packet: split_options.on_packet,
+            split_stream: split_options.split_stream,
+            stream_time_bases,
+            split_stream_bytes: 0,
+            split_stream_time: 0,
             start_time: Instant::now(),
             started: false,
             need_key_frame,
             split_wait_for_key_frame: false,
-        })
+            split_requested: false,
+            next_max_files: None,
+            next_max_size_bytes: None,
+            next_max_size_time: None,
+            fragment_start_pts: None,
+            fragment_end_pts: None,
+            last_segment_duration: None,
+        };
+        if split_options.resume.unwrap_or(false) && writer.format_location.is_none() {
+            if let Some(last_index) = writer.last_index_on_disk() {
+                // Continue the last file if appending, otherwise start a new one.
+                let next_index = if writer.append {
+                    last_index
+                } else {
+                    last_index + 1
+                };
+                writer.current_index = writer.start_index.max(next_index);
+            }
+        }
+        Ok(writer)
     }
 
-    /// Returns `true` if `writer.size() >= max_size_bytes`.
-    pub(crate) fn is_bytes_overrun(&mut self) -> bool {
-        let mut exceeded = false;
-        if let Some(ref writer) = self.writer {
-            if self.max_size_bytes > 0 && writer.size() >= self.max_size_bytes {
-                exceeded = true
-            }
+    /// Returns the number of the streams to write, see `SimpleWriter::stream_count()`.
+    pub fn stream_count(&self) -> usize {
+        self.stream_codecs.len()
+    }
+
+    /// Returns the `stream_index` to write the frames of the media by its
+    /// index in the `descs`, see `SimpleWriter::output_stream_index()`.
+    pub fn output_stream_index(&self, desc_index: usize) -> Option<usize> {
+        media_stream_indexes(self.medias.iter().map(Deref::deref))
+            .get(desc_index)
+            .copied()
+            .flatten()
+    }
+
+    /// Returns the size of the current fragment, of the `split_stream` only if specified.
+    fn fragment_bytes(&self) -> u64 {
+        match self.split_stream {
+            Some(_) => self.split_stream_bytes,
+            None => self.writer.as_ref().map(|x| x.size()).unwrap_or(0),
         }
-        exceeded
     }
 
-    /// Returns `true` if `writer.size() >= max_size_bytes * (1.0 + max_overhead)`.
-    pub(crate) fn is_bytes_overflow(&mut self) -> bool {
-        let mut exceeded = false;
-        if let Some(ref writer) = self.writer {
-            let overhead_bytes = self.max_size_bytes * (self.max_overhead * 100.0) as u64 / 100;
-            if self.max_size_bytes > 0 && writer.size() >= self.max_size_bytes + overhead_bytes {
-                exceeded = true
+    /// Returns the time of the current fragment, of the `split_stream` only if specified.
+    fn fragment_time(&self) -> Duration {
+        match self.split_stream {
+            Some(_) => Duration::from_nanos(self.split_stream_time),
+            None => self.start_time.elapsed(),
+        }
+    }
+
+    /// Count the frame of the `split_stream`.
+    fn update_split_stream(&mut self, size: usize, duration: i64, stream_index: usize) {
+        if self.split_stream != Some(stream_index) {
+            return;
+        }
+        self.split_stream_bytes += size as u64;
+        if let Some(&time_base) = self.stream_time_bases.get(stream_index) {
+            if time_base.num > 0 && duration > 0 {
+                if let Some(duration) = pts_to_duration(duration, time_base) {
+                    self.split_stream_time += duration.as_nanos() as u64;
+                }
             }
         }
-        exceeded
+    }
+
+    /// Track the pts range of the frames in the current fragment.
+    fn update_fragment_pts(&mut self, pts: i64, duration: i64, stream_index: usize) {
+        let time_base = match self.stream_time_bases.get(stream_index) {
+            Some(&time_base) if time_base.num > 0 && pts != AV_NOPTS_VALUE => time_base,
+            _ => return,
+        };
+        let start = rescale_ts(pts, time_base, NANOS_TIME_BASE);
+        let end = rescale_ts(pts + duration.max(0), time_base, NANOS_TIME_BASE);
+        self.fragment_start_pts = Some(self.fragment_start_pts.map_or(start, |x| x.min(start)));
+        self.fragment_end_pts = Some(self.fragment_end_pts.map_or(end, |x| x.max(end)));
+    }
+
+    /// Returns `true` if `fragment_bytes() >= max_size_bytes`.
+    pub(crate) fn is_bytes_overrun(&mut self) -> bool {
+        self.writer.is_some()
+            && self.max_size_bytes > 0
+            && self.fragment_bytes() >= self.max_size_bytes
+    }
+
+    /// Returns `true` if `fragment_bytes() >= max_size_bytes * (1.0 + max_overhead)`.
+    pub(crate) fn is_bytes_overflow(&mut self) -> bool {
+        let overhead_bytes = self.max_size_bytes * (self.max_overhead * 100.0) as u64 / 100;
+        self.writer.is_some()
+            && self.max_size_bytes > 0
+            && self.fragment_bytes() >= self.max_size_bytes + overhead_bytes
     }
 
     /// Returns `true` if `time >= max_size_time`.
     pub(crate) fn is_time_overrun(&mut self) -> bool {
-        self.max_size_time > 0
-            && self.start_time.elapsed() >= Duration::from_nanos(self.max_size_time)
+        self.max_size_time > 0 && self.fragment_time() >= Duration::from_nanos(self.max_size_time)
     }
 
     /// Returns `true` if `time >= max_size_time * (1.0 + max_overhead)`.
     pub(crate) fn is_time_overflow(&mut self) -> bool {
         let overhead_time = self.max_size_time * (self.max_overhead * 100.0) as u64 / 100;
         self.max_size_time > 0
-            && self.start_time.elapsed() >= Duration::from_nanos(self.max_size_time + overhead_time)
+            && self.fragment_time() >= Duration::from_nanos(self.max_size_time + overhead_time)
     }
 
     /// Return `true` if can split fragment now.
@@ -554,9 +1996,10 @@ impl SplitWriter {
         let mut split_now: bool = false;
         if self.split_wait_for_key_frame {
             split_now = self.stream_has_key_frame(stream_index) && is_key_frame;
-            self.split_wait_for_key_frame = false;
+            // The forced split keeps waiting until the key frame arrived.
+            self.split_wait_for_key_frame = !split_now && self.split_requested;
         } else {
-            let overrun = self.is_bytes_overrun() || self.is_time_overrun();
+            let overrun = self.split_requested || self.is_bytes_overrun() || self.is_time_overrun();
             if overrun && self.split_at_keyframe && self.need_key_frame {
                 self.split_wait_for_key_frame = true;
             } else {
@@ -567,26 +2010,160 @@ impl SplitWriter {
         split_now || overflow
     }
 
-    /// Clean older files.
-    pub fn clean_files(&self) {
-        if self.max_files > 0 && (self.current_index - self.start_index) >= self.max_files - 1 {
-            let index = self.current_index - (self.max_files - 1);
-            if index >= self.start_index {
-                let old_file = self.format_location(index);
-                std::fs::remove_file(old_file).unwrap();
+    /// Start a new fragment on the next frame, or on the next key frame
+    /// if `split_at_keyframe = true`.
+    ///
+    /// Does nothing if no frame written to the current fragment.
+    pub fn force_split(&mut self) {
+        if self.writer.is_none() {
+            return;
+        }
+        self.split_requested = true;
+        if self.split_at_keyframe && self.need_key_frame {
+            self.split_wait_for_key_frame = true;
+        }
+    }
+
+    /// Change the maximum number of files to keep on disk, 0 to disable.
+    ///
+    /// Applied at the next split, not to the fragments done. The fragments
+    /// beyond the new maximum written before are not deleted.
+    pub fn set_max_files(&mut self, max_files: usize) {
+        self.next_max_files = Some(max_files);
+        self.apply_next_limits_if_idle();
+    }
+
+    /// Change the max amount of data per file (in bytes, 0=disable).
+    ///
+    /// Applied at the next split, the current fragment keeps the old limit.
+    pub fn set_max_size_bytes(&mut self, max_size_bytes: u64) {
+        self.next_max_size_bytes = Some(max_size_bytes);
+        self.apply_next_limits_if_idle();
+    }
+
+    /// Change the max amount of time per file (in ns, 0=disable).
+    ///
+    /// Applied at the next split, the current fragment keeps the old limit.
+    pub fn set_max_size_time(&mut self, max_size_time: u64) {
+        self.next_max_size_time = Some(max_size_time);
+        self.apply_next_limits_if_idle();
+    }
+
+    /// Apply the changed limits immediately if no fragment in writing.
+    fn apply_next_limits_if_idle(&mut self) {
+        if self.writer.is_none() {
+            self.apply_next_limits();
+        }
+    }
+
+    /// Apply the limits changed by the setters.
+    fn apply_next_limits(&mut self) {
+        if let Some(max_files) = self.next_max_files.take() {
+            self.max_files = max_files;
+        }
+        if let Some(max_size_bytes) = self.next_max_size_bytes.take() {
+            self.max_size_bytes = max_size_bytes;
+        }
+        if let Some(max_size_time) = self.next_max_size_time.take() {
+            self.max_size_time = max_size_time;
+        }
+    }
+
+    /// Clean older files.
+    pub fn clean_files(&self) {
+        if self.max_files > 0 && (self.current_index - self.start_index) >= self.max_files - 1 {
+            let index = self.current_index - (self.max_files - 1);
+            if index >= self.start_index {
+                let old_file = self.format_location(index);
+                // The file may be removed by others, the split goes on.
+                if let Err(err) = std::fs::remove_file(&old_file) {
+                    log_warn!("Failed to remove {:?}: {}", old_file, err);
+                }
+            }
+        }
+    }
+
+    /// Returns the locations of the fragments retained on disk, oldest first.
+    ///
+    /// The current fragment is included, it may be still in writing.
+    pub fn retained_files(&self) -> Vec<PathBuf> {
+        let first = if self.max_files > 0 {
+            self.current_index
+                .saturating_sub(self.max_files - 1)
+                .max(self.start_index)
+        } else {
+            self.start_index
+        };
+        (first..=self.current_index)
+            .map(|index| self.format_location(index))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    /// Concatenate the fragments retained on disk into a single file.
+    ///
+    /// Used to save the last N fragments when an event triggered, only
+    /// the `mpegts` format is supported, the fragments are concatenated
+    /// bytes by bytes.
+    pub fn snapshot(&self, dest: &Path) -> AVResult<()> {
+        match self.format.as_deref() {
+            Some("mpegts") => {
+                let mut output = std::fs::File::create(dest)?;
+                for path in self.retained_files() {
+                    let mut input = std::fs::File::open(path)?;
+                    std::io::copy(&mut input, &mut output)?;
+                }
+                output.sync_all()?;
+                Ok(())
             }
+            format => Err(format!("Snapshot does not support format: {:?}", format).into()),
         }
     }
 
+    /// Returns the highest fragment index of the files exists in `output_path`.
+    ///
+    /// Only the files with default naming (`MED000000.ext`) are recognized.
+    pub(crate) fn last_index_on_disk(&self) -> Option<usize> {
+        let ext = self.segment_extension();
+        std::fs::read_dir(&self.output_path)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                name.strip_prefix("MED")?
+                    .strip_suffix(ext)?
+                    .parse::<usize>()
+                    .ok()
+            })
+            .max()
+    }
+
     /// Returns the extension of the format.
     pub fn ext_of_format(format: Option<&str>) -> &'static str {
         format
             .map(|s| match s {
                 "mp4" => ".mp4",
+                "mov" => ".mov",
                 "mpegts" => ".ts",
-                _ => "dat",
+                "matroska" => ".mkv",
+                "webm" => ".webm",
+                "flv" => ".flv",
+                "avi" => ".avi",
+                "ipod" => ".m4v",
+                "3gp" => ".3gp",
+                "h264" => ".264",
+                "hevc" => ".265",
+                _ => ".dat",
             })
-            .unwrap_or("dat")
+            .unwrap_or(".dat")
+    }
+
+    /// Returns the extension of the fragment files, the `segment_extension`
+    /// specified or derived from the format.
+    pub fn segment_extension(&self) -> &str {
+        self.segment_extension
+            .as_deref()
+            .unwrap_or_else(|| Self::ext_of_format(self.format.as_deref()))
     }
 
     /// Returns the fragment file location.
@@ -594,237 +2171,2192 @@ impl SplitWriter {
         let loc = if let Some(ref cb) = self.format_location {
             cb(index)
         } else {
-            format!(
-                "MED{:06}{}",
-                index,
-                Self::ext_of_format(self.format.as_deref())
-            )
+            format!("MED{:06}{}", index, self.segment_extension())
         };
         let path = self.output_path.join(loc);
         if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).unwrap();
+            // The error is reported by opening the file.
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                log_warn!("Failed to create {:?}: {}", parent, err);
+            }
         }
         path
     }
 
     /// Close the output file and create a new one.
     pub fn split_now(&mut self) {
+        let closed_path = self.current_path();
         if let Some(ref cb) = self.before_split {
-            cb(self.current_index);
+            cb(self.current_index, &closed_path);
         }
         let _ = self.writer.take();
+        if let (Some(start), Some(end)) =
+            (self.fragment_start_pts.take(), self.fragment_end_pts.take())
+        {
+            self.last_segment_duration = Some(Duration::from_nanos((end - start).max(0) as u64));
+        }
+        self.split_stream_bytes = 0;
+        self.split_stream_time = 0;
+        self.split_requested = false;
+        self.split_wait_for_key_frame = false;
+        self.apply_next_limits();
         self.clean_files();
         self.current_index += 1;
         if let Some(ref cb) = self.after_split {
-            cb(self.current_index);
+            cb(self.current_index, &closed_path);
         }
     }
 
+    /// Returns the duration of the last fragment closed by split, from the
+    /// earliest pts to the end of the latest frame written to it.
+    ///
+    /// Updated before the `after_split` callback called, `None` if no fragment
+    /// closed yet or the frames of it have no timestamps.
+    pub fn last_segment_duration(&self) -> Option<Duration> {
+        self.last_segment_duration
+    }
+
+    /// Returns the index of the current fragment.
+    pub fn current_index(&self) -> usize {
+        self.current_index
+    }
+
+    /// Returns the location of the current fragment.
+    pub fn current_path(&self) -> PathBuf {
+        self.format_location(self.current_index)
+    }
+
     /// Return `true` if the stream has `key_frame` props.
     pub fn stream_has_key_frame(&self, stream_index: usize) -> bool {
-        self.medias[stream_index].codec_id().has_gop()
+        match self.stream_codecs.get(stream_index) {
+            Some(codec_id) => codec_has_gop(*codec_id),
+            None => false,
+        }
     }
 }
 
-/// Options Builder for the SimpleWriter.
-#[derive(Default)]
-pub struct OpenOptions {
-    medias: Vec<Box<dyn MediaDesc>>,
-    format: Option<String>,
-    format_options: Option<String>,
-    format_location: Option<Box<FormatLocationCallback>>,
-    before_split: Option<Box<SplitNotifier>>,
-    after_split: Option<Box<SplitNotifier>>,
-    max_files: Option<usize>,
-    max_size_bytes: Option<u64>,
-    max_size_time: Option<u64>,
-    max_overhead: Option<f32>,
-    split_at_keyframe: Option<bool>,
-    start_index: Option<usize>,
+/// Thread safe Writer, can be shared between threads by `Arc<SyncWriter>`.
+///
+/// The calls are serialized by an internal mutex, but the interleaving of the
+/// muxer still requires the dts of the frames to be monotonic across the threads.
+pub struct SyncWriter {
+    inner: Mutex<Box<dyn Writer + Send>>,
 }
 
-impl Debug for OpenOptions {
+impl Debug for SyncWriter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "OpenOptions @ 0x{:p}", self)
+        write!(f, "SyncWriter @ 0x{:p}", self)
     }
 }
 
-impl OpenOptions {
-    /// Create an new Options Builder for the SimpleWriter.
-    pub fn new() -> Self {
-        Default::default()
+impl Writer for SyncWriter {
+    fn write_header(&mut self) -> AVResult<()> {
+        SyncWriter::write_header(self)
     }
 
-    /// Add a media description to the output format.
-    pub fn media<T>(mut self, media: T) -> Self
-    where
-        T: MediaDesc + Sized + 'static,
-    {
-        self.medias.push(Box::new(media));
-        self
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        SyncWriter::write_bytes(self, bytes, pts, duration, is_key_frame, stream_index)
     }
 
-    /// Specified the muxing format of the output format.
-    pub fn format<S>(mut self, format: S) -> Self
-    where
-        S: Into<String>,
-    {
-        self.format = Some(format.into());
-        self
+    fn write_bytes_with_dts(
+        &mut self,
+        bytes: &[u8],
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        SyncWriter::write_bytes_with_dts(
+            self,
+            bytes,
+            pts,
+            dts,
+            duration,
+            is_key_frame,
+            stream_index,
+        )
     }
 
-    /// Specified the muxing format options of the output format.
-    pub fn format_options<S>(mut self, format_options: S) -> Self
-    where
-        S: Into<String>,
-    {
-        self.format_options = Some(format_options.into());
-        self
+    #[cfg(feature = "bytes")]
+    fn write_bytes_ref(
+        &mut self,
+        data: Bytes,
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.lock()
+            .write_bytes_ref(data, pts, duration, is_key_frame, stream_index)
     }
 
-    /// Callback for returns the location to be used for the next output file.
-    pub fn format_location<F>(mut self, format_location: F) -> Self
-    where
-        F: Fn(usize) -> String + 'static,
-    {
-        self.format_location = Some(Box::new(format_location));
-        self
+    fn write_trailer(&mut self) -> AVResult<()> {
+        SyncWriter::write_trailer(self)
     }
 
-    /// Callback before split fragment.
-    pub fn before_split<F>(mut self, before_split: F) -> Self
-    where
-        F: Fn(usize) + 'static,
-    {
-        self.before_split = Some(Box::new(before_split));
-        self
+    fn close(&mut self) {
+        SyncWriter::close(self)
     }
 
-    /// Callback after split fragment.
-    pub fn after_split<F>(mut self, after_split: F) -> Self
-    where
-        F: Fn(usize) + 'static,
-    {
-        self.after_split = Some(Box::new(after_split));
-        self
+    fn finish(self: Box<Self>) -> AVResult<()> {
+        self.into_inner().finish()
     }
 
-    /// Maximum number of files to keep on disk.
-    pub fn max_files(mut self, max_files: usize) -> Self {
-        self.max_files = Some(max_files);
-        self
+    fn flush(&mut self) {
+        SyncWriter::flush(self)
     }
 
-    /// Max amount of data per file (in bytes, 0=disable).
-    pub fn max_size_bytes(mut self, max_size_bytes: u64) -> Self {
-        self.max_size_bytes = Some(max_size_bytes);
-        self
+    fn size(&self) -> u64 {
+        SyncWriter::size(self)
     }
 
-    /// Max amount of time per file (in ns, 0=disable).
-    pub fn max_size_time(mut self, max_size_time: u64) -> Self {
-        self.max_size_time = Some(max_size_time);
-        self
+    fn force_split(&mut self) {
+        SyncWriter::force_split(self)
     }
 
-    /// Extra size/time overhead of muxing (0.02 = 2%).
-    pub fn max_overhead(mut self, max_overhead: f32) -> Self {
-        self.max_overhead = Some(max_overhead);
+    fn as_any(&self) -> &dyn Any {
         self
     }
 
-    /// Split immediately if `split_at_keyframe = false`.
-    /// The option ignored when `size > max_size_bytes + max_size_bytes * max_overhead`
-    /// or `time > max_size_time + max_size_time * max_overhead`.
-    pub fn split_at_keyframe(mut self, split_at_keyframe: bool) -> Self {
-        self.split_at_keyframe = Some(split_at_keyframe);
+    fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+}
 
-    /// Start value of fragment index.
-    pub fn start_index(mut self, start_index: usize) -> Self {
-        self.start_index = Some(start_index);
-        self
+impl SyncWriter {
+    /// Create a new thread safe writer wraps the `writer`, which must be
+    /// `Send` to be moved between the threads, like: `SimpleWriter`.
+    pub fn new(writer: Box<dyn Writer + Send>) -> Self {
+        Self {
+            inner: Mutex::new(writer),
+        }
     }
 
-    /// Open the output file and returns the SimpleWriter.
-    pub fn open<P>(self, path: P) -> AVResult<Box<dyn Writer>>
-    where
-        P: AsRef<Path> + Sized,
-    {
-        if self.format_location.is_some() || self.max_files.is_some() {
-            let split_options = SplitOptions {
-                output_path: Some(AsRef::<Path>::as_ref(&path).to_path_buf()),
-                format_location: self.format_location,
-                before_split: self.before_split,
-                after_split: self.after_split,
-                max_files: self.max_files,
-                max_size_bytes: self.max_size_bytes,
-                max_size_time: self.max_size_time,
-                max_overhead: self.max_overhead,
-                split_at_keyframe: self.split_at_keyframe,
-                start_index: self.start_index,
-            };
-            let writer = SplitWriter::new(
-                self.medias,
-                self.format.as_deref(),
-                self.format_options.as_deref(),
-                split_options,
-            )?;
-            Ok(Box::new(writer))
-        } else {
-            let medias: Vec<&dyn MediaDesc> = self.medias.iter().map(Deref::deref).collect();
-            let writer = SimpleWriter::new(
-                path,
-                &medias[..],
-                self.format.as_deref(),
-                self.format_options.as_deref(),
-            )?;
-            Ok(Box::new(writer))
-        }
+    /// Write the header of the format to the stream.
+    pub fn write_header(&self) -> AVResult<()> {
+        self.lock().write_header()
+    }
+
+    /// Write frame bytes to the stream, see `Writer::write_bytes()`.
+    pub fn write_bytes(
+        &self,
+        bytes: &[u8],
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.lock()
+            .write_bytes(bytes, pts, duration, is_key_frame, stream_index)
+    }
+
+    /// Write frame bytes with the decompression timestamp to the stream,
+    /// see `Writer::write_bytes_with_dts()`.
+    pub fn write_bytes_with_dts(
+        &self,
+        bytes: &[u8],
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.lock()
+            .write_bytes_with_dts(bytes, pts, dts, duration, is_key_frame, stream_index)
+    }
+
+    /// Write the trailer of the format to the stream.
+    pub fn write_trailer(&self) -> AVResult<()> {
+        self.lock().write_trailer()
+    }
+
+    /// Close all resouces accessed by the muxer.
+    pub fn close(&self) {
+        self.lock().close()
+    }
+
+    /// Flush all buffered data to stream destionation.
+    pub fn flush(&self) {
+        self.lock().flush()
+    }
+
+    /// Returns the size of the stream processed.
+    pub fn size(&self) -> u64 {
+        self.lock().size()
+    }
+
+    /// Request to start a new fragment, see `SplitWriter::force_split()`.
+    pub fn force_split(&self) {
+        self.lock().force_split()
+    }
+
+    /// Consumes the wrapper, returning the underlying writer.
+    pub fn into_inner(self) -> Box<dyn Writer + Send> {
+        self.inner.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Box<dyn Writer + Send>> {
+        // The writer is still usable if another thread panicked while writing.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Writer fans out the frames to multiple writers, like: remuxing one input
+/// to mp4 and mpegts at the same time.
+///
+/// In the fail-fast mode (default), the first error of the writers is returned
+/// immediately, the rest writers are skipped for that call. In the best-effort
+/// mode, the failed writers are dropped and the rest continue, the errors are
+/// recorded in `failures()` and returned only if all writers failed.
+pub struct TeeWriter {
+    writers: Vec<Option<Box<dyn Writer>>>,
+    best_effort: bool,
+    failures: Vec<(usize, String)>,
+}
 
-    #[test]
-    fn test_simple_writer() {
-        let a_desc = AudioDesc::new();
-        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
-        let example_bytes = include_bytes!("../../examples/envivio-352x288.264.framed");
-        for _ in 0..100 {
-            let mut mp4_writer = SimpleWriter::new(
-                "/tmp/envivio-352x288.264.mp4",
-                &[&a_desc, &v_desc],
-                None,
-                Some("movflags=frag_keyframe"),
-            )
-            .unwrap();
-            let mut ts_writer = SimpleWriter::new(
-                "/tmp/envivio-352x288.264.ts",
-                &[&a_desc, &v_desc],
+impl Debug for TeeWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TeeWriter @ 0x{:p}", self)
+    }
+}
+
+impl Writer for TeeWriter {
+    fn write_header(&mut self) -> AVResult<()> {
+        self.for_each(|w| w.write_header())
+    }
+
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.for_each(|w| w.write_bytes(bytes, pts, duration, is_key_frame, stream_index))
+    }
+
+    fn write_bytes_with_dts(
+        &mut self,
+        bytes: &[u8],
+        pts: i64,
+        dts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.for_each(|w| {
+            w.write_bytes_with_dts(bytes, pts, dts, duration, is_key_frame, stream_index)
+        })
+    }
+
+    #[cfg(feature = "bytes")]
+    fn write_bytes_ref(
+        &mut self,
+        data: Bytes,
+        pts: i64,
+        duration: i64,
+        is_key_frame: bool,
+        stream_index: usize,
+    ) -> AVResult<()> {
+        self.for_each(|w| {
+            w.write_bytes_ref(data.clone(), pts, duration, is_key_frame, stream_index)
+        })
+    }
+
+    fn write_trailer(&mut self) -> AVResult<()> {
+        self.for_each(|w| w.write_trailer())
+    }
+
+    fn close(&mut self) {
+        for writer in self.writers.iter_mut().flatten() {
+            writer.close();
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> AVResult<()> {
+        // All writers are finished even if some of them failed.
+        let mut errors = vec![];
+        for (index, writer) in self.writers.drain(..).enumerate() {
+            if let Some(writer) = writer {
+                if let Err(err) = writer.finish() {
+                    errors.push(format!("Writer #{}: {}", index, err));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; ").into())
+        }
+    }
+
+    fn flush(&mut self) {
+        for writer in self.writers.iter_mut().flatten() {
+            writer.flush();
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.writers.iter().flatten().map(|x| x.size()).sum()
+    }
+
+    fn force_split(&mut self) {
+        for writer in self.writers.iter_mut().flatten() {
+            writer.force_split();
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl TeeWriter {
+    /// Create a new writer fans out to the `writers`, in the fail-fast mode.
+    pub fn new(writers: Vec<Box<dyn Writer>>) -> Self {
+        Self {
+            writers: writers.into_iter().map(Some).collect(),
+            best_effort: false,
+            failures: vec![],
+        }
+    }
+
+    /// Keep writing to the rest writers if some of them failed.
+    pub fn best_effort(mut self, best_effort: bool) -> Self {
+        self.best_effort = best_effort;
+        self
+    }
+
+    /// Add a writer to fan out, the index is the number of the writers added before.
+    pub fn push(&mut self, writer: Box<dyn Writer>) {
+        self.writers.push(Some(writer));
+    }
+
+    /// Returns the writer at index, `None` if it failed in the best-effort mode.
+    pub fn get(&self, index: usize) -> Option<&(dyn Writer + 'static)> {
+        self.writers.get(index).and_then(|x| x.as_deref())
+    }
+
+    /// Returns the number of the writers still working.
+    pub fn len(&self) -> usize {
+        self.writers.iter().flatten().count()
+    }
+
+    /// Returns true if no writer is working.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the index and the error of the writers failed in the best-effort mode.
+    pub fn failures(&self) -> &[(usize, String)] {
+        &self.failures
+    }
+
+    fn for_each<F>(&mut self, mut f: F) -> AVResult<()>
+    where
+        F: FnMut(&mut dyn Writer) -> AVResult<()>,
+    {
+        let mut failed = false;
+        for (index, slot) in self.writers.iter_mut().enumerate() {
+            if let Some(writer) = slot {
+                if let Err(err) = f(writer.as_mut()) {
+                    if !self.best_effort {
+                        return Err(format!("Writer #{}: {}", index, err).into());
+                    }
+                    self.failures.push((index, err.to_string()));
+                    *slot = None;
+                    failed = true;
+                }
+            }
+        }
+        if failed && self.is_empty() {
+            let errors: Vec<String> = self
+                .failures
+                .iter()
+                .map(|(index, err)| format!("Writer #{}: {}", index, err))
+                .collect();
+            Err(errors.join("; ").into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Options Builder for the SimpleWriter.
+#[derive(Default)]
+pub struct OpenOptions {
+    medias: Vec<Box<dyn MediaDesc>>,
+    format: Option<String>,
+    format_options: Option<String>,
+    format_location: Option<Box<FormatLocationCallback>>,
+    before_split: Option<Box<SplitNotifier>>,
+    after_split: Option<Box<SplitNotifier>>,
+    max_files: Option<usize>,
+    max_size_bytes: Option<u64>,
+    max_size_time: Option<u64>,
+    max_overhead: Option<f32>,
+    split_at_keyframe: Option<bool>,
+    start_index: Option<usize>,
+    resume: Option<bool>,
+    stream_offsets: Vec<(usize, i64)>,
+    cover_art: Option<(Vec<u8>, String)>,
+    timecode: Option<(String, AVRational)>,
+    segment_extension: Option<String>,
+    append: Option<bool>,
+    fix_dts: Option<bool>,
+    on_packet: Option<Arc<PacketInspector>>,
+    split_stream: Option<usize>,
+    mpegts_muxrate: Option<u64>,
+    pcr_period_ms: Option<u32>,
+    flush_packets: Option<bool>,
+}
+
+impl Debug for OpenOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OpenOptions @ 0x{:p}", self)
+    }
+}
+
+impl OpenOptions {
+    /// Create an new Options Builder for the SimpleWriter.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a media description to the output format.
+    pub fn media<T>(mut self, media: T) -> Self
+    where
+        T: MediaDesc + Sized + 'static,
+    {
+        self.medias.push(Box::new(media));
+        self
+    }
+
+    /// Specified the muxing format of the output format.
+    pub fn format<S>(mut self, format: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.format = Some(format.into());
+        self
+    }
+
+    /// Specified the muxing format options of the output format.
+    ///
+    /// For mp4, the `movflags=faststart` moves the moov atom to the front when
+    /// the trailer written, it requires a seekable file output and is incompatible
+    /// with the fragmented mode for streaming (`frag_keyframe` or `empty_moov`).
+    pub fn format_options<S>(mut self, format_options: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.format_options = Some(format_options.into());
+        self
+    }
+
+    /// Constant mux rate (in bit/s) of the mpegts, for the decoders require CBR.
+    ///
+    /// The output is padded with null packets to the rate, which must be
+    /// higher than the total bitrate of the streams.
+    pub fn mpegts_muxrate(mut self, muxrate: u64) -> Self {
+        self.mpegts_muxrate = Some(muxrate);
+        self
+    }
+
+    /// PCR retransmission period (in ms) of the mpegts.
+    pub fn pcr_period_ms(mut self, pcr_period_ms: u32) -> Self {
+        self.pcr_period_ms = Some(pcr_period_ms);
+        self
+    }
+
+    /// Flush the I/O context after each packet (`AVFMT_FLAG_FLUSH_PACKETS`),
+    /// for live streaming.
+    ///
+    /// Reduces the latency of the output at the cost of more and smaller writes.
+    pub fn flush_packets(mut self, flush_packets: bool) -> Self {
+        self.flush_packets = Some(flush_packets);
+        self
+    }
+
+    /// Returns the format options with the typed muxer options appended.
+    fn muxer_options(&self) -> Option<String> {
+        let mut options: Vec<String> = self.format_options.iter().cloned().collect();
+        if let Some(muxrate) = self.mpegts_muxrate {
+            options.push(format!("muxrate={}", muxrate));
+        }
+        if let Some(pcr_period_ms) = self.pcr_period_ms {
+            options.push(format!("pcr_period={}", pcr_period_ms));
+        }
+        let mut options = options.join(":");
+        if let Some(flush_packets) = self.flush_packets {
+            let sign = if flush_packets { '+' } else { '-' };
+            options = append_flag(&options, "fflags", &format!("{}flush_packets", sign));
+        }
+        if options.is_empty() {
+            None
+        } else {
+            Some(options)
+        }
+    }
+
+    /// Callback for returns the location to be used for the next output file.
+    pub fn format_location<F>(mut self, format_location: F) -> Self
+    where
+        F: Fn(usize) -> String + 'static,
+    {
+        self.format_location = Some(Box::new(format_location));
+        self
+    }
+
+    /// Callback before split fragment, with the index and location of the fragment to be closed.
+    pub fn before_split<F>(mut self, before_split: F) -> Self
+    where
+        F: Fn(usize, &Path) + 'static,
+    {
+        self.before_split = Some(Box::new(before_split));
+        self
+    }
+
+    /// Callback after split fragment, with the index of the next fragment
+    /// and the location of the fragment just closed.
+    pub fn after_split<F>(mut self, after_split: F) -> Self
+    where
+        F: Fn(usize, &Path) + 'static,
+    {
+        self.after_split = Some(Box::new(after_split));
+        self
+    }
+
+    /// Maximum number of files to keep on disk.
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    /// Max amount of data per file (in bytes, 0=disable).
+    pub fn max_size_bytes(mut self, max_size_bytes: u64) -> Self {
+        self.max_size_bytes = Some(max_size_bytes);
+        self
+    }
+
+    /// Max amount of time per file (in ns, 0=disable).
+    pub fn max_size_time(mut self, max_size_time: u64) -> Self {
+        self.max_size_time = Some(max_size_time);
+        self
+    }
+
+    /// Extra size/time overhead of muxing (0.02 = 2%).
+    pub fn max_overhead(mut self, max_overhead: f32) -> Self {
+        self.max_overhead = Some(max_overhead);
+        self
+    }
+
+    /// Measure the `max_size_bytes` and `max_size_time` by the frames of the
+    /// stream only, instead of the output size and the wall clock time.
+    ///
+    /// For example: rotate by the video bytes, ignoring the audio and the
+    /// metadata. The time is the sum of the frame durations. The `max_overhead`
+    /// is applied to the totals of the stream too, the other streams and the
+    /// muxing overhead are not limited.
+    pub fn split_stream(mut self, stream_index: usize) -> Self {
+        self.split_stream = Some(stream_index);
+        self
+    }
+
+    /// Split immediately if `split_at_keyframe = false`.
+    /// The option ignored when `size > max_size_bytes + max_size_bytes * max_overhead`
+    /// or `time > max_size_time + max_size_time * max_overhead`.
+    pub fn split_at_keyframe(mut self, split_at_keyframe: bool) -> Self {
+        self.split_at_keyframe = Some(split_at_keyframe);
+        self
+    }
+
+    /// Start value of fragment index.
+    pub fn start_index(mut self, start_index: usize) -> Self {
+        self.start_index = Some(start_index);
+        self
+    }
+
+    /// Continue the fragment index from the last file exists on disk.
+    ///
+    /// The files in the output path are scanned on open and the writing
+    /// begins at the index past the highest one found, instead of
+    /// overwriting from `start_index`.
+    ///
+    /// Resume only works with the default naming (`MED000000.ext`), it has
+    /// no effect when `format_location` is specified.
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = Some(resume);
+        self
+    }
+
+    /// Append to the existing file instead of truncating it.
+    ///
+    /// This only makes sense for the formats can be concatenated, like: mpegts.
+    /// With `resume`, the writing continues the last fragment on disk.
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = Some(append);
+        self
+    }
+
+    /// Bump the non-increasing dts of the frames instead of failing to write,
+    /// see `SimpleWriter::set_fix_dts()`.
+    pub fn fix_dts(mut self, fix_dts: bool) -> Self {
+        self.fix_dts = Some(fix_dts);
+        self
+    }
+
+    /// Callback invoked with each packet right before muxing, see
+    /// `SimpleWriter::set_on_packet()`.
+    pub fn on_packet<F>(mut self, on_packet: F) -> Self
+    where
+        F: Fn(&AVPacket) + Send + Sync + 'static,
+    {
+        self.on_packet = Some(Arc::new(on_packet));
+        self
+    }
+
+    /// Apply a constant offset (in ns, may be negative) to the timestamps of the stream.
+    pub fn stream_offset(mut self, stream_index: usize, offset_ns: i64) -> Self {
+        self.stream_offsets.push((stream_index, offset_ns));
+        self
+    }
+
+    /// Embed a JPEG or PNG picture as the cover art, like: `image/jpeg`.
+    pub fn cover_art<B, S>(mut self, bytes: B, mime: S) -> Self
+    where
+        B: Into<Vec<u8>>,
+        S: Into<String>,
+    {
+        self.cover_art = Some((bytes.into(), mime.into()));
+        self
+    }
+
+    /// Embed the SMPTE timecode of the first frame, like: `01:00:00:00`.
+    ///
+    /// Only the mp4 and mov formats write the timecode (tmcd) track, the same
+    /// timecode is embedded in each fragment of the `SplitWriter`.
+    pub fn timecode<S>(mut self, timecode: S, frame_rate: AVRational) -> Self
+    where
+        S: Into<String>,
+    {
+        self.timecode = Some((timecode.into(), frame_rate));
+        self
+    }
+
+    /// Extension of the fragment files with the default naming, like: `.mkv`.
+    ///
+    /// The extension derived from the format is used if not specified.
+    pub fn segment_extension<S>(mut self, segment_extension: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.segment_extension = Some(segment_extension.into());
+        self
+    }
+
+    /// Validate the options without creating any files.
+    ///
+    /// The `path` is only used to guess the format if not specified. Returns
+    /// error if the format is unknown, the format options can't be parsed, the
+    /// time base of a media is invalid or a codec can't be stored in the format.
+    pub fn validate<P>(&self, path: P) -> AVResult<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| format!("Invalid path: {:?}", path.as_ref()))?;
+        let cpath = CString::new(path)?;
+        let cformat = CString::new(self.format.as_deref().unwrap_or(""))?;
+        let ofmt = unsafe {
+            av_guess_format(
+                match self.format {
+                    Some(_) => cformat.as_ptr(),
+                    None => std::ptr::null(),
+                },
+                cpath.as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        if ofmt.is_null() {
+            return Err(match self.format {
+                Some(ref format) => format!("Unknown output format: {}", format).into(),
+                None => format!("Unable to guess the output format of {}", path).into(),
+            });
+        }
+        let medias: Vec<&dyn MediaDesc> = self.medias.iter().map(Deref::deref).collect();
+        check_medias(&medias, self.muxer_options().as_deref())?;
+        let format_name = unsafe { CStr::from_ptr((*ofmt).name).to_string_lossy() };
+        for media in self.medias.iter().filter(|x| has_stream(x.as_ref())) {
+            let codec_id = media.codec_id();
+            // Negative if the muxer can't tell, leave it to the muxer.
+            let ret = unsafe { avformat_query_codec(ofmt, codec_id, FF_COMPLIANCE_NORMAL as _) };
+            if ret == 0 {
+                return Err(format!(
+                    "The codec {:?} is not supported by the format {}",
+                    codec_id, format_name
+                )
+                .into());
+            }
+        }
+        if let Some((ref timecode, frame_rate)) = self.timecode {
+            check_timecode(timecode, frame_rate)?;
+        }
+        Ok(())
+    }
+
+    /// Open the output file and returns the SimpleWriter.
+    pub fn open<P>(self, path: P) -> AVResult<Box<dyn Writer>>
+    where
+        P: AsRef<Path> + Sized,
+    {
+        let format_options = self.muxer_options();
+        if self.format_location.is_some() || self.max_files.is_some() {
+            let split_options = SplitOptions {
+                output_path: Some(AsRef::<Path>::as_ref(&path).to_path_buf()),
+                format_location: self.format_location,
+                before_split: self.before_split,
+                after_split: self.after_split,
+                max_files: self.max_files,
+                max_size_bytes: self.max_size_bytes,
+                max_size_time: self.max_size_time,
+                max_overhead: self.max_overhead,
+                split_at_keyframe: self.split_at_keyframe,
+                start_index: self.start_index,
+                resume: self.resume,
+                stream_offsets: self.stream_offsets,
+                cover_art: self.cover_art,
+                timecode: self.timecode,
+                segment_extension: self.segment_extension,
+                append: self.append,
+                fix_dts: self.fix_dts,
+                on_packet: self.on_packet,
+                split_stream: self.split_stream,
+            };
+            let writer = SplitWriter::new(
+                self.medias,
+                self.format.as_deref(),
+                format_options.as_deref(),
+                split_options,
+            )?;
+            Ok(Box::new(writer))
+        } else {
+            let medias: Vec<&dyn MediaDesc> = self.medias.iter().map(Deref::deref).collect();
+            let format = self.format.as_deref();
+            let format_options = format_options.as_deref();
+            let mut writer = if self.append.unwrap_or(false) {
+                SimpleWriter::append(path, &medias[..], format, format_options)?
+            } else {
+                SimpleWriter::new(path, &medias[..], format, format_options)?
+            };
+            for &(index, offset) in self.stream_offsets.iter() {
+                writer.set_stream_offset(index, offset)?;
+            }
+            if let Some((ref bytes, ref mime)) = self.cover_art {
+                writer.set_cover_art(bytes, mime)?;
+            }
+            if let Some((ref timecode, frame_rate)) = self.timecode {
+                writer.set_timecode(timecode, frame_rate)?;
+            }
+            writer.set_fix_dts(self.fix_dts.unwrap_or(false));
+            writer.set_on_packet(self.on_packet);
+            Ok(Box::new(writer))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy::fixture::example_frames;
+
+    #[test]
+    fn test_simple_writer() {
+        let a_desc = AudioDesc::new();
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let example_bytes = include_bytes!("../../examples/envivio-352x288.264.framed");
+        for _ in 0..100 {
+            let mut mp4_writer = SimpleWriter::new(
+                "/tmp/envivio-352x288.264.mp4",
+                &[&a_desc, &v_desc],
+                None,
+                Some("movflags=frag_keyframe"),
+            )
+            .unwrap();
+            let mut ts_writer = SimpleWriter::new(
+                "/tmp/envivio-352x288.264.ts",
+                &[&a_desc, &v_desc],
                 Some("mpegts"),
                 Some("mpegts_copyts=1"),
             )
             .unwrap();
             let mut offset: usize = 0;
             let mut pts = 0;
-            while offset + 4 < example_bytes.len() {
-                let size_bytes = &example_bytes[offset..offset + 4];
-                let frame_size = i32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
-                offset += 4;
-                let frame_bytes = &example_bytes[offset..offset + frame_size];
-                offset += frame_size;
-                mp4_writer
+            while offset + 4 < example_bytes.len() {
+                let size_bytes = &example_bytes[offset..offset + 4];
+                let frame_size = i32::from_be_bytes(size_bytes.try_into().unwrap()) as usize;
+                offset += 4;
+                let frame_bytes = &example_bytes[offset..offset + frame_size];
+                offset += frame_size;
+                mp4_writer
+                    .write_bytes(frame_bytes, pts, 40000, false, 0)
+                    .unwrap();
+                ts_writer
+                    .write_bytes(frame_bytes, pts, 40000, false, 0)
+                    .unwrap();
+                pts += 40000;
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_simple_writer_drop_on_failure() {
+        // The writes to `/dev/full` fail even for the root, unlike a read-only dir.
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new("/dev/full", &[&v_desc], Some("mpegts"), None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            if writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .is_err()
+            {
+                break;
+            }
+            pts += 40000;
+        }
+        // The trailer fails on dropping, without panic.
+        drop(writer);
+    }
+
+    #[test]
+    fn test_simple_writer_flush_fragment() {
+        let path = "/tmp/ffav-flush-fragment.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer =
+            SimpleWriter::new(path, &[&v_desc], None, Some("movflags=cmaf+frag_custom")).unwrap();
+        writer.flush_fragment().unwrap();
+        let mut pts = 0;
+        let mut frames = 0;
+        for frame_bytes in example_frames().take(50) {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, frames == 0, 0)
+                .unwrap();
+            pts += 40000;
+            frames += 1;
+            if frames % 10 == 0 {
+                writer.flush_fragment().unwrap();
+            }
+        }
+        Box::new(writer).finish().unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        let moofs = bytes.windows(4).filter(|x| x == b"moof").count();
+        assert!(moofs >= 5);
+
+        let mut writer = SimpleWriter::new_in_memory(&[&v_desc], "mpegts", None).unwrap();
+        assert!(writer.flush_fragment().is_err());
+    }
+
+    #[test]
+    fn test_simple_writer_in_memory() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new_in_memory(&[&v_desc], "mpegts", None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        let bytes = writer.into_bytes().unwrap();
+        assert_eq!(bytes.len() % 188, 0);
+        assert!(bytes.chunks(188).all(|x| x[0] == 0x47));
+
+        let writer = SimpleWriter::new("/tmp/ffav-in-memory.ts", &[&v_desc], None, None).unwrap();
+        assert!(writer.into_bytes().is_err());
+    }
+
+    #[test]
+    fn test_tee_writer() {
+        struct BrokenWriter;
+        impl Writer for BrokenWriter {
+            fn write_header(&mut self) -> AVResult<()> {
+                Err("Broken".into())
+            }
+            fn write_bytes(&mut self, _: &[u8], _: i64, _: i64, _: bool, _: usize) -> AVResult<()> {
+                Err("Broken".into())
+            }
+            fn write_trailer(&mut self) -> AVResult<()> {
+                Err("Broken".into())
+            }
+            fn close(&mut self) {}
+            fn flush(&mut self) {}
+            fn size(&self) -> u64 {
+                0
+            }
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                self
+            }
+        }
+
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mp4_writer = OpenOptions::new()
+            .media(v_desc)
+            .open("/tmp/ffav-tee.mp4")
+            .unwrap();
+        let ts_writer = OpenOptions::new()
+            .media(v_desc)
+            .format("mpegts")
+            .open("/tmp/ffav-tee.ts")
+            .unwrap();
+        let mut writer =
+            TeeWriter::new(vec![mp4_writer, ts_writer, Box::new(BrokenWriter)]).best_effort(true);
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        assert_eq!(writer.len(), 2);
+        assert_eq!(writer.failures().len(), 1);
+        assert_eq!(writer.failures()[0].0, 2);
+        assert!(writer.get(2).is_none());
+        assert!(writer
+            .get(0)
+            .and_then(|x| x.downcast_ref::<SimpleWriter>())
+            .is_some());
+        assert!(writer.size() > 0);
+        Box::new(writer).finish().unwrap();
+        for path in ["/tmp/ffav-tee.mp4", "/tmp/ffav-tee.ts"].iter() {
+            let reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+            assert!(reader.stream(0).is_some());
+        }
+
+        let mut writer = TeeWriter::new(vec![Box::new(BrokenWriter)]);
+        assert!(writer.write_bytes(&[0], 0, 40000, true, 0).is_err());
+        assert!(writer.failures().is_empty());
+        let mut writer = writer.best_effort(true);
+        assert!(writer.write_bytes(&[0], 0, 40000, true, 0).is_err());
+        assert!(writer.is_empty());
+    }
+
+    #[test]
+    fn test_simple_writer_checksum() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        // The mp4 muxer seeks back to rewrite the size of the boxes.
+        for path in ["/tmp/ffav-checksum.ts", "/tmp/ffav-checksum.mp4"].iter() {
+            let mut writer = SimpleWriter::with_checksum(path, &[&v_desc], None, None).unwrap();
+            let mut pts = 0;
+            for frame_bytes in example_frames() {
+                writer
+                    .write_bytes(frame_bytes, pts, 40000, false, 0)
+                    .unwrap();
+                pts += 40000;
+            }
+            writer.write_trailer().unwrap();
+            writer.flush();
+            let bytes = std::fs::read(path).unwrap();
+            assert_eq!(writer.checksum().unwrap(), u64::from(crc32(0, &bytes)));
+        }
+        assert_eq!(crc32(0, b"123456789"), 0xcbf43926);
+        let writer = SimpleWriter::new("/tmp/ffav-checksum.ts", &[&v_desc], None, None).unwrap();
+        assert!(writer.checksum().is_err());
+    }
+
+    #[test]
+    fn test_rotation_remux() {
+        let mut v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        v_desc.rotation = Some(90.0);
+        let mut mp4_writer =
+            SimpleWriter::new("/tmp/envivio-352x288.rotated.mp4", &[&v_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            mp4_writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        mp4_writer.close();
+
+        let reader =
+            crate::easy::SimpleReader::open("/tmp/envivio-352x288.rotated.mp4", None, None)
+                .unwrap();
+        let stream = reader.stream(0).unwrap();
+        assert_eq!(stream.rotation_degrees().map(f64::round), Some(90.0));
+        let desc = VideoDesc::from_stream(stream);
+        assert_eq!(desc.rotation.map(f64::round), Some(90.0));
+        assert_eq!((desc.width, desc.height), (352, 288));
+        assert_eq!(desc.pix_fmt, AV_PIX_FMT_YUV420P);
+    }
+
+    #[test]
+    fn test_remux_b_frames() {
+        // Returns the timestamps of the first stream relative to the first dts.
+        fn read_timestamps(path: &str) -> Vec<(i64, i64)> {
+            let mut reader = crate::easy::SimpleReader::open(path, None, Some(1000000)).unwrap();
+            let mut timestamps = Vec::new();
+            while let Some(packet) = reader.read_frame() {
+                if packet.stream_index == 0 {
+                    timestamps.push((packet.pts, packet.dts));
+                }
+            }
+            let first_dts = timestamps[0].1;
+            timestamps
+                .into_iter()
+                .map(|(pts, dts)| (pts - first_dts, dts - first_dts))
+                .collect()
+        }
+
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut mp4_writer =
+            SimpleWriter::new("/tmp/ffav-b-frames.mp4", &[&v_desc], None, None).unwrap();
+        let mut expected = Vec::new();
+        for (frame_bytes, index) in example_frames().zip(0..) {
+            // The decoding order of I0 P3 B1 B2 P6 B4 B5 ...
+            let display = match index {
+                0 => 0,
+                i if (i - 1) % 3 == 0 => i + 2,
+                i => i - 1,
+            };
+            let pts = (display + 1) * 40000;
+            let dts = index * 40000;
+            mp4_writer
+                .write_bytes_with_dts(frame_bytes, pts, dts, 40000, index == 0, 0)
+                .unwrap();
+            expected.push((pts, dts));
+        }
+        Box::new(mp4_writer).finish().unwrap();
+        assert_eq!(read_timestamps("/tmp/ffav-b-frames.mp4"), expected);
+
+        let mut reader =
+            crate::easy::SimpleReader::open("/tmp/ffav-b-frames.mp4", None, Some(1000000)).unwrap();
+        // Pausing is supported by the network streams only.
+        assert!(reader.pause().is_err());
+        assert!(reader.play().is_err());
+        let mut ts_writer =
+            SimpleWriter::new("/tmp/ffav-b-frames.ts", &[&v_desc], None, None).unwrap();
+        while let Some(packet) = reader.read_frame() {
+            ts_writer
+                .write_bytes_with_dts(
+                    packet.data(),
+                    packet.pts,
+                    packet.dts,
+                    packet.duration,
+                    packet.is_key_frame(),
+                    0,
+                )
+                .unwrap();
+        }
+        Box::new(ts_writer).finish().unwrap();
+        assert_eq!(read_timestamps("/tmp/ffav-b-frames.ts"), expected);
+    }
+
+    #[test]
+    fn test_output_time_base() {
+        // The mp4 honors the time base, but the mpegts always uses 90KHz.
+        for &(path, time_base, expected) in [
+            (
+                "/tmp/ffav-output-time-base.mp4",
+                AVRational::new(1, 90000),
+                90000,
+            ),
+            (
+                "/tmp/ffav-output-time-base.ts",
+                AVRational::new(1, 1000),
+                90000,
+            ),
+        ]
+        .iter()
+        {
+            let mut v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+            v_desc.output_time_base = Some(time_base);
+            let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+            assert_eq!(writer.stream_time_base(0).unwrap().den, time_base.den);
+            let mut pts = 0;
+            for frame_bytes in example_frames() {
+                writer
                     .write_bytes(frame_bytes, pts, 40000, false, 0)
                     .unwrap();
-                ts_writer
-                    .write_bytes(frame_bytes, pts, 40000, false, 0)
+                pts += 40000;
+            }
+            assert_eq!(writer.stream_time_base(0).unwrap().den, expected);
+            assert_eq!(writer.stream_time_base(1), None);
+            Box::new(writer).finish().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_webm_writer() {
+        let path = "/tmp/ffav-vp9-opus.webm";
+        let v_desc = VideoDesc::with_vp9(352, 288, 4000, 1000000);
+        let a_desc = AudioDesc::with_opus(48000, 2, 64000);
+        let mut writer = OpenOptions::new()
+            .media(v_desc)
+            .media(a_desc)
+            .format("webm")
+            .open(path)
+            .unwrap();
+        // The uncompressed header of VP9 profile 0 key frame, and the
+        // 20ms silence of Opus.
+        let mut vp9_frame = vec![0x82, 0x49, 0x83, 0x42, 0x00, 0x15, 0xf0, 0x11, 0xf0];
+        vp9_frame.resize(64, 0);
+        let opus_frame = [0xfc, 0xff, 0xfe];
+        for i in 0..50 {
+            writer
+                .write_bytes(&vp9_frame, i * 40000, 40000, true, 0)
+                .unwrap();
+            writer
+                .write_bytes(&opus_frame, i * 1920, 960, true, 1)
+                .unwrap();
+            writer
+                .write_bytes(&opus_frame, i * 1920 + 960, 960, true, 1)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        let codec_ids: Vec<AVCodecID> = (0..reader.nb_streams())
+            .filter_map(|i| reader.stream(i)?.codecpar().map(|x| x.codec_id))
+            .collect();
+        assert_eq!(codec_ids, vec![AV_CODEC_ID_VP9, AV_CODEC_ID_OPUS]);
+    }
+
+    #[test]
+    fn test_any_video_codec() {
+        struct UnknownDesc;
+        impl MediaDesc for UnknownDesc {
+            fn codec_id(&self) -> AVCodecID {
+                AV_CODEC_ID_BIN_DATA
+            }
+        }
+
+        let mjpeg_frame = [0xff, 0xd8, 0xff, 0xe0, 0x00, 0x02, 0xff, 0xd9];
+        let mpeg2_frame = [0x00, 0x00, 0x01, 0xb3, 0x16, 0x01, 0x20, 0x13];
+        for &(path, format, codec_id, frame) in [
+            (
+                "/tmp/ffav-mjpeg.mkv",
+                "matroska",
+                AV_CODEC_ID_MJPEG,
+                &mjpeg_frame,
+            ),
+            (
+                "/tmp/ffav-mpeg2.ts",
+                "mpegts",
+                AV_CODEC_ID_MPEG2VIDEO,
+                &mpeg2_frame,
+            ),
+        ]
+        .iter()
+        {
+            let v_desc = VideoDesc {
+                codec_id,
+                width: 352,
+                height: 288,
+                time_base: AVRational::new(1, 1000000),
+                pix_fmt: AV_PIX_FMT_YUV420P,
+                ..Default::default()
+            };
+            let mut writer = SimpleWriter::new(path, &[&v_desc], Some(format), None).unwrap();
+            for i in 0..25 {
+                writer
+                    .write_bytes(frame, i * 40000, 40000, true, 0)
+                    .unwrap();
+            }
+            Box::new(writer).finish().unwrap();
+            let reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+            let par = reader.stream(0).unwrap().codecpar().unwrap();
+            assert_eq!(par.codec_id, codec_id);
+        }
+
+        assert!(SimpleWriter::new("/tmp/ffav-unknown.mkv", &[&UnknownDesc], None, None).is_err());
+    }
+
+    #[test]
+    fn test_encoder_name() {
+        let path = "/tmp/ffav-encoder-name.mkv";
+        let mut v_desc = VideoDesc {
+            codec_id: AV_CODEC_ID_MJPEG,
+            width: 352,
+            height: 288,
+            time_base: AVRational::new(1, 1000000),
+            pix_fmt: AV_PIX_FMT_YUVJ420P,
+            encoder_name: Some("mjpeg"),
+            ..Default::default()
+        };
+        let writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        Box::new(writer).finish().unwrap();
+        // The encoder of the other codec.
+        v_desc.encoder_name = Some("png");
+        assert!(SimpleWriter::new(path, &[&v_desc], None, None).is_err());
+        v_desc.encoder_name = Some("nonexistent");
+        assert!(SimpleWriter::new(path, &[&v_desc], None, None).is_err());
+    }
+
+    #[test]
+    fn test_video_desc_new_encoder() {
+        let mut v_desc = VideoDesc {
+            codec_id: AV_CODEC_ID_MJPEG,
+            width: 352,
+            height: 288,
+            time_base: AVRational::new(1, 25),
+            gop_size: 12,
+            pix_fmt: AV_PIX_FMT_YUVJ420P,
+            codec_options: Some("g=25:huffman=optimal"),
+            ..Default::default()
+        };
+        let encoder = v_desc.new_encoder().unwrap();
+        assert_eq!(encoder.gop_size, 25);
+        // The options must not silently vanish.
+        v_desc.codec_options = Some("g=25:nonexistent=1");
+        let err = v_desc.new_encoder().unwrap_err();
+        assert!(err.to_string().contains("nonexistent"));
+    }
+
+    #[test]
+    fn test_timed_id3_writer() {
+        let path = "/tmp/ffav-timed-id3.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let d_desc = DataDesc::with_timed_id3(1000000);
+        let mut writer = OpenOptions::new()
+            .media(v_desc)
+            .media(d_desc)
+            .format("mpegts")
+            .open(path)
+            .unwrap();
+        // The empty ID3v2.4 tag.
+        let id3_tag = [b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 0];
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            if pts % 1000000 == 0 {
+                writer.write_bytes(&id3_tag, pts, 0, true, 1).unwrap();
+            }
+            pts += 40000;
+        }
+        writer.finish().unwrap();
+
+        let mut reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        let par = reader.stream(1).unwrap().codecpar().unwrap();
+        assert_eq!(par.codec_type, AVMEDIA_TYPE_DATA);
+        assert_eq!(par.codec_id, AV_CODEC_ID_TIMED_ID3);
+        let tags = reader.frames().filter(|(x, _)| x.stream_index == 1).count();
+        assert!(tags > 0);
+    }
+
+    #[test]
+    fn test_image2_writer() {
+        let output_path = PathBuf::from("/tmp/ffav-image2");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        let v_desc = VideoDesc {
+            codec_id: AV_CODEC_ID_PNG,
+            width: 352,
+            height: 288,
+            time_base: AVRational::new(1, 25),
+            pix_fmt: AV_PIX_FMT_RGB24,
+            ..Default::default()
+        };
+        let mut writer = OpenOptions::new()
+            .media(v_desc)
+            .format("image2")
+            .open(output_path.join("frame_%05d.png"))
+            .unwrap();
+        for pts in 0..3 {
+            writer
+                .write_bytes(b"\x89PNG\r\n\x1a\n", pts, 1, true, 0)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        assert!(output_path.join("frame_00001.png").exists());
+        assert!(output_path.join("frame_00003.png").exists());
+
+        let path = output_path.join("frame.png");
+        assert!(SimpleWriter::new(&path, &[&v_desc], Some("image2"), None).is_err());
+        assert!(SimpleWriter::new(&path, &[&v_desc], Some("image2"), Some("update=1")).is_ok());
+    }
+
+    #[test]
+    fn test_output_stream_index() {
+        let a_desc = AudioDesc::new();
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let d_desc = DataDesc::with_klv(1000000);
+        let mut writer = SimpleWriter::new(
+            "/tmp/ffav-stream-index.ts",
+            &[&a_desc, &v_desc, &d_desc],
+            Some("mpegts"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(writer.stream_count(), 2);
+        assert_eq!(writer.output_stream_index(0), None);
+        assert_eq!(writer.output_stream_index(1), Some(0));
+        assert_eq!(writer.output_stream_index(2), Some(1));
+        assert_eq!(writer.output_stream_index(3), None);
+        assert!(writer.write_bytes(&[0u8; 16], 0, 40000, true, 2).is_err());
+
+        let split_options = SplitOptions {
+            output_path: Some(PathBuf::from("/tmp/ffav-split-stream-index")),
+            ..Default::default()
+        };
+        let writer = SplitWriter::new(
+            vec![Box::new(a_desc), Box::new(v_desc), Box::new(d_desc)],
+            Some("mpegts"),
+            None,
+            split_options,
+        )
+        .unwrap();
+        assert_eq!(writer.stream_count(), 2);
+        assert_eq!(writer.output_stream_index(0), None);
+        assert_eq!(writer.output_stream_index(2), Some(1));
+    }
+
+    #[test]
+    fn test_faststart_sink() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let sink = SharedBuffer::new();
+        assert!(SimpleWriter::with_faststart_sink(sink.clone(), &[&v_desc], None, None).is_err());
+        let mut writer = SimpleWriter::with_faststart_sink(
+            sink.clone(),
+            &[&v_desc],
+            Some("mp4"),
+            Some("movflags=faststart"),
+        )
+        .unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        assert!(sink.is_empty());
+        Box::new(writer).finish().unwrap();
+        let bytes = sink.take();
+        let find = |tag: &[u8]| bytes.windows(4).position(|x| x == tag).unwrap();
+        assert!(find(b"moov") < find(b"mdat"));
+    }
+
+    #[test]
+    fn test_non_seekable_size() {
+        struct PipeSink;
+        impl std::io::Write for PipeSink {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        impl std::io::Seek for PipeSink {
+            fn seek(&mut self, _pos: std::io::SeekFrom) -> std::io::Result<u64> {
+                Err(std::io::ErrorKind::Other.into())
+            }
+        }
+
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let io = AVIOContextOwned::with_writer(PipeSink).unwrap();
+        let ctx = AVFormatContextOwned::with_output_io("", Some("mpegts"), io).unwrap();
+        let mut writer = SimpleWriter::with_context(ctx, &[&v_desc], None).unwrap();
+        let mut pts = 0;
+        let mut last_size = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            assert!(writer.size() >= last_size);
+            last_size = writer.size();
+            pts += 40000;
+        }
+        assert!(last_size > 0);
+        Box::new(writer).finish().unwrap();
+    }
+
+    #[test]
+    fn test_simple_writer_set_extradata() {
+        let path = "/tmp/ffav-extradata.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        // The first frame starts with the SPS/PPS.
+        let first_frame = example_frames().next().unwrap();
+        let sps_pps_size = first_frame
+            .windows(4)
+            .position(|x| x[0..3] == [0, 0, 1] && (x[3] & 0x1f) == 5)
+            .unwrap();
+        let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        assert!(writer
+            .set_extradata(1, &first_frame[..sps_pps_size])
+            .is_err());
+        writer
+            .set_extradata(0, &first_frame[..sps_pps_size])
+            .unwrap();
+        writer.write_bytes(first_frame, 0, 40000, true, 0).unwrap();
+        assert!(writer
+            .set_extradata(0, &first_frame[..sps_pps_size])
+            .is_err());
+        Box::new(writer).finish().unwrap();
+
+        let reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        let par = reader.stream(0).unwrap().codecpar().unwrap();
+        assert!(par.extradata_size > 0);
+    }
+
+    #[test]
+    fn test_split_writer_resume() {
+        let output_path = PathBuf::from("/tmp/ffav-split-resume");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        std::fs::write(output_path.join("MED000005.ts"), b"").unwrap();
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let split_options = SplitOptions {
+            output_path: Some(output_path.clone()),
+            max_files: Some(10),
+            resume: Some(true),
+            ..Default::default()
+        };
+        let writer =
+            SplitWriter::new(vec![Box::new(v_desc)], Some("mpegts"), None, split_options).unwrap();
+        assert_eq!(
+            writer.format_location(writer.current_index),
+            output_path.join("MED000006.ts")
+        );
+    }
+
+    #[test]
+    fn test_split_writer_multi_video() {
+        let output_path = PathBuf::from("/tmp/ffav-split-multi");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        let a_desc = AudioDesc::with_aac(48000, 2, 64000);
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        // The audio and the empty desc come first, the video streams are 1 and 2.
+        let mut writer = OpenOptions::new()
+            .media(AudioDesc::new())
+            .media(a_desc)
+            .media(v_desc)
+            .media(v_desc)
+            .format("mpegts")
+            .max_files(100)
+            .max_size_bytes(64 * 1024)
+            .max_overhead(10.0)
+            .open(&output_path)
+            .unwrap();
+        // ADTS header of AAC LC, 48000 Hz, stereo with 64 bytes payload.
+        let frame_len = 7 + 64;
+        let mut adts_frame = vec![0xff, 0xf1, 0x4c, 0x80, (frame_len >> 3) as u8];
+        adts_frame.extend_from_slice(&[((frame_len & 7) << 5) as u8 | 0x1f, 0xfc]);
+        adts_frame.resize(frame_len, 0);
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            let key = frame_bytes
+                .windows(4)
+                .any(|x| x[0..3] == [0, 0, 1] && (x[3] & 0x1f) == 5);
+            writer
+                .write_bytes(&adts_frame, pts * 48 / 1000, 1024, true, 0)
+                .unwrap();
+            writer.write_bytes(frame_bytes, pts, 40000, key, 1).unwrap();
+            writer.write_bytes(frame_bytes, pts, 40000, key, 2).unwrap();
+            pts += 40000;
+        }
+        let split_writer = writer.downcast_ref::<SplitWriter>().unwrap();
+        assert!(split_writer.current_index() > 0);
+        assert!(writer.downcast_ref::<SimpleWriter>().is_none());
+        writer.finish().unwrap();
+        let reader =
+            crate::easy::SimpleReader::open(output_path.join("MED000000.ts"), None, None).unwrap();
+        assert_eq!(reader.nb_streams(), 3);
+        assert!(reader.channel_layout(0).is_some());
+        assert_eq!(reader.channel_layout(1), None);
+        assert!(output_path.join("MED000001.ts").exists());
+    }
+
+    #[test]
+    fn test_split_writer_force_split() {
+        let output_path = PathBuf::from("/tmp/ffav-split-force");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let split_options = SplitOptions {
+            output_path: Some(output_path.clone()),
+            ..Default::default()
+        };
+        let mut writer =
+            SplitWriter::new(vec![Box::new(v_desc)], Some("mpegts"), None, split_options).unwrap();
+        let mut pts = 0;
+        for (frames, frame_bytes) in example_frames().enumerate() {
+            // Waits for the key frame to split.
+            let is_key_frame = frames == 0 || frames == 20;
+            writer
+                .write_bytes(frame_bytes, pts, 40000, is_key_frame, 0)
+                .unwrap();
+            if frames == 10 {
+                Writer::force_split(&mut writer);
+            }
+            if frames == 19 {
+                assert_eq!(writer.current_index, 0);
+            }
+            pts += 40000;
+        }
+        assert_eq!(writer.current_index, 1);
+        Box::new(writer).finish().unwrap();
+        assert!(output_path.join("MED000001.ts").exists());
+    }
+
+    #[test]
+    fn test_split_writer_set_limits() {
+        let output_path = PathBuf::from("/tmp/ffav-split-limits");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let split_options = SplitOptions {
+            output_path: Some(output_path.clone()),
+            split_at_keyframe: Some(false),
+            ..Default::default()
+        };
+        let mut writer =
+            SplitWriter::new(vec![Box::new(v_desc)], Some("mpegts"), None, split_options).unwrap();
+        let mut pts = 0;
+        for (frames, frame_bytes) in example_frames().enumerate() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, frames == 0, 0)
+                .unwrap();
+            if frames == 0 {
+                // Each frame in a fragment from the next split.
+                writer.set_max_size_bytes(1);
+                writer.set_max_files(3);
+            }
+            if frames == 9 {
+                assert_eq!(writer.current_index(), 0);
+                writer.force_split();
+            }
+            pts += 40000;
+        }
+        assert!(writer.current_index() > 10);
+        assert!(writer.retained_files().len() <= 3);
+        Box::new(writer).finish().unwrap();
+    }
+
+    #[test]
+    fn test_split_writer_last_segment_duration() {
+        let output_path = PathBuf::from("/tmp/ffav-split-duration");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let split_options = SplitOptions {
+            output_path: Some(output_path.clone()),
+            split_at_keyframe: Some(false),
+            ..Default::default()
+        };
+        let mut writer =
+            SplitWriter::new(vec![Box::new(v_desc)], Some("mpegts"), None, split_options).unwrap();
+        let mut pts = 0;
+        for (frames, frame_bytes) in example_frames().enumerate() {
+            if frames == 25 {
+                assert_eq!(writer.last_segment_duration(), None);
+                writer.force_split();
+            }
+            writer
+                .write_bytes(frame_bytes, pts, 40000, frames == 0, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        // The first 25 frames of 40ms.
+        assert_eq!(writer.current_index(), 1);
+        assert_eq!(
+            writer.last_segment_duration(),
+            Some(Duration::from_millis(1000))
+        );
+        Box::new(writer).finish().unwrap();
+    }
+
+    #[test]
+    fn test_split_writer_split_stream() {
+        let output_path = PathBuf::from("/tmp/ffav-split-stream");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = OpenOptions::new()
+            .media(v_desc)
+            .media(v_desc)
+            .format("mpegts")
+            .max_files(100)
+            .max_size_bytes(64 * 1024)
+            .split_at_keyframe(false)
+            .split_stream(1)
+            .open(&output_path)
+            .unwrap();
+        for stream_index in 0..2 {
+            let mut pts = 0;
+            for frame_bytes in example_frames() {
+                writer
+                    .write_bytes(frame_bytes, pts, 40000, false, stream_index)
                     .unwrap();
                 pts += 40000;
             }
+            // The bytes of the stream 0 are not counted.
+            let split_writer = writer.downcast_ref::<SplitWriter>().unwrap();
+            if stream_index == 0 {
+                assert_eq!(split_writer.current_index(), 0);
+            } else {
+                assert!(split_writer.current_index() > 0);
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_split_writer_snapshot() {
+        let output_path = PathBuf::from("/tmp/ffav-split-snapshot");
+        let _ = std::fs::remove_dir_all(&output_path);
+        std::fs::create_dir_all(&output_path).unwrap();
+        for (index, bytes) in [b"0000", b"1111", b"2222", b"3333"].iter().enumerate() {
+            std::fs::write(output_path.join(format!("MED{:06}.ts", index)), bytes).unwrap();
+        }
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let split_options = SplitOptions {
+            output_path: Some(output_path.clone()),
+            max_files: Some(3),
+            ..Default::default()
+        };
+        let mut writer =
+            SplitWriter::new(vec![Box::new(v_desc)], Some("mpegts"), None, split_options).unwrap();
+        writer.current_index = 3;
+        let dest = output_path.join("snapshot.ts");
+        writer.snapshot(&dest).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"111122223333");
+    }
+
+    #[test]
+    fn test_sync_writer() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let writer = SimpleWriter::new(
+            "/tmp/ffav-sync-writer.ts",
+            &[&v_desc, &v_desc],
+            Some("mpegts"),
+            None,
+        )
+        .unwrap();
+        let writer = std::sync::Arc::new(SyncWriter::new(Box::new(writer)));
+        let handles: Vec<_> = (0..2)
+            .map(|stream_index| {
+                let writer = writer.clone();
+                std::thread::spawn(move || {
+                    let mut pts = 0;
+                    for frame_bytes in example_frames() {
+                        writer
+                            .write_bytes(frame_bytes, pts, 40000, false, stream_index)
+                            .unwrap();
+                        pts += 40000;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let writer = std::sync::Arc::try_unwrap(writer).unwrap();
+        Box::new(writer).finish().unwrap();
+    }
+
+    #[test]
+    fn test_stream_offset() {
+        let path = "/tmp/ffav-stream-offset.ts";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer =
+            SimpleWriter::new(path, &[&v_desc, &v_desc], Some("mpegts"), None).unwrap();
+        writer.set_stream_offset(1, 1_000_000_000).unwrap();
+        assert!(writer.set_stream_offset(2, 0).is_err());
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            for stream_index in 0..2 {
+                writer
+                    .write_bytes(frame_bytes, pts, 40000, pts == 0, stream_index)
+                    .unwrap();
+            }
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let mut reader = crate::easy::SimpleReader::open(path, None, Some(1000000)).unwrap();
+        let mut start_pts = [i64::MAX; 2];
+        while let Some(packet) = reader.read_frame() {
+            let start = &mut start_pts[packet.stream_index as usize];
+            *start = (*start).min(packet.pts);
+        }
+        assert_eq!(start_pts[1] - start_pts[0], 1000000);
+    }
+
+    #[test]
+    fn test_add_stream_from() {
+        // Returns the codec, size and extradata of the first stream.
+        fn stream_params(path: &str) -> (AVCodecID, i32, i32, Vec<u8>) {
+            let reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+            let par = reader.streams()[0].codecpar().unwrap();
+            let extradata = unsafe {
+                std::slice::from_raw_parts(par.extradata, par.extradata_size as usize).to_vec()
+            };
+            (par.codec_id, par.width, par.height, extradata)
+        }
+
+        let src_path = "/tmp/ffav-copy-src.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(src_path, &[&v_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, pts == 0, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+
+        // Remux the length prefixed frames of mp4 as is.
+        let path = "/tmp/ffav-copy.mkv";
+        let mut reader = crate::easy::ReaderOptions::new()
+            .skip_bsf(true)
+            .open(src_path)
+            .unwrap();
+        let mut writer = SimpleWriter::new(path, &[], Some("matroska"), None).unwrap();
+        let stream_index = writer.add_stream_from(reader.streams()[0]).unwrap();
+        assert_eq!(stream_index, 0);
+        let mut count = 0;
+        while let Some(packet) = reader.read_frame() {
+            writer
+                .write_bytes(
+                    packet.data(),
+                    packet.pts,
+                    packet.duration,
+                    packet.is_key_frame(),
+                    stream_index,
+                )
+                .unwrap();
+            count += 1;
+        }
+        assert!(writer.add_stream_from(reader.streams()[0]).is_err());
+        Box::new(writer).finish().unwrap();
+        assert_eq!(count, example_frames().count());
+
+        let (codec_id, width, height, extradata) = stream_params(path);
+        assert_eq!((codec_id, width, height), (AV_CODEC_ID_H264, 352, 288));
+        assert!(!extradata.is_empty());
+        assert_eq!(extradata, stream_params(src_path).3);
+        let mut reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        let mut count = 0;
+        while reader.read_frame().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, example_frames().count());
+    }
+
+    #[test]
+    fn test_simple_writer_vfr() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new("/tmp/ffav-vfr.mp4", &[&v_desc], None, None).unwrap();
+        let durations = [33333, 40000, 1, 0, 66666];
+        let mut pts = 0;
+        for (i, frame_bytes) in example_frames().enumerate() {
+            let duration = durations[i % durations.len()];
+            writer
+                .write_bytes(frame_bytes, pts, duration, false, 0)
+                .unwrap();
+            pts += duration.max(1000);
+        }
+        Box::new(writer).finish().unwrap();
+    }
+
+    #[test]
+    fn test_simple_writer_subtitle() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let s_desc = SubtitleDesc::with_mov_text(1000000);
+        let mut writer =
+            SimpleWriter::new("/tmp/ffav-subtitle.mp4", &[&v_desc, &s_desc], None, None).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            if pts % 1000000 == 0 {
+                let text = SubtitleDesc::encode_mov_text(&format!("{} s", pts / 1000000));
+                writer.write_bytes(&text, pts, 1000000, true, 1).unwrap();
+            }
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+    }
+
+    #[test]
+    fn test_ext_of_format() {
+        let cases = [
+            (Some("mp4"), ".mp4"),
+            (Some("mov"), ".mov"),
+            (Some("mpegts"), ".ts"),
+            (Some("matroska"), ".mkv"),
+            (Some("webm"), ".webm"),
+            (Some("flv"), ".flv"),
+            (Some("avi"), ".avi"),
+            (Some("ipod"), ".m4v"),
+            (Some("3gp"), ".3gp"),
+            (Some("h264"), ".264"),
+            (Some("hevc"), ".265"),
+            (Some("unknown"), ".dat"),
+            (None, ".dat"),
+        ];
+        for (format, ext) in cases.iter() {
+            assert_eq!(SplitWriter::ext_of_format(*format), *ext);
+        }
+    }
+
+    #[test]
+    fn test_segment_extension() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let split_options = SplitOptions {
+            output_path: Some(PathBuf::from("/tmp/ffav-split-ext")),
+            max_files: Some(10),
+            segment_extension: Some(".tsv".into()),
+            ..Default::default()
+        };
+        let writer =
+            SplitWriter::new(vec![Box::new(v_desc)], Some("mpegts"), None, split_options).unwrap();
+        assert_eq!(writer.segment_extension(), ".tsv");
+        assert_eq!(
+            writer.current_path(),
+            PathBuf::from("/tmp/ffav-split-ext/MED000000.tsv")
+        );
+    }
+
+    #[test]
+    fn test_writer_stats() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer =
+            SimpleWriter::new("/tmp/ffav-stats.ts", &[&v_desc], Some("mpegts"), None).unwrap();
+        let frame_bytes = [0u8, 0, 0, 1, 0x09, 0xf0];
+        for i in 0..10 {
+            writer
+                .write_bytes(&frame_bytes, i * 40000, 40000, false, 0)
+                .unwrap();
+        }
+        let stats = writer.stats();
+        assert_eq!(stats.packets, 10);
+        assert_eq!(stats.bytes, 60);
+
+        let path = "/tmp/ffav-stats-direct.ts";
+        let mut writer = SimpleWriter::new(path, &[&v_desc], Some("mpegts"), None).unwrap();
+        writer.set_interleaved(false);
+        writer.set_auto_flush(false);
+        for (frame_bytes, i) in example_frames().take(10).zip(0..) {
+            writer
+                .write_bytes(frame_bytes, i * 40000, 40000, i == 0, 0)
+                .unwrap();
+        }
+        assert_eq!(writer.stats().packets, 10);
+        Box::new(writer).finish().unwrap();
+        let mut reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        assert_eq!(reader.frames().count(), 10);
+    }
+
+    #[test]
+    fn test_simple_writer_append() {
+        let path = "/tmp/ffav-append.ts";
+        let _ = std::fs::remove_file(path);
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let frame_bytes = [0u8, 0, 0, 1, 0x09, 0xf0];
+        let mut sizes = vec![];
+        for _ in 0..2 {
+            let mut writer = SimpleWriter::append(path, &[&v_desc], Some("mpegts"), None).unwrap();
+            for i in 0..10 {
+                writer
+                    .write_bytes(&frame_bytes, i * 40000, 40000, false, 0)
+                    .unwrap();
+            }
+            Box::new(writer).finish().unwrap();
+            sizes.push(std::fs::metadata(path).unwrap().len());
+        }
+        assert_eq!(sizes[1], sizes[0] * 2);
+    }
+
+    #[test]
+    fn test_time_base_validation() {
+        assert_eq!(VideoDesc::with_h264(352, 288, 4000, 0).time_base.den, 90000);
+        assert_eq!(
+            VideoDesc::with_h265(352, 288, 4000, -1).time_base.den,
+            90000
+        );
+        assert_eq!(SubtitleDesc::with_mov_text(0).time_base.den, 90000);
+        assert_eq!(
+            VideoDesc::with_h264(352, 288, 4000, 1000).time_base.den,
+            1000
+        );
+
+        // The existing file is kept if the description is invalid.
+        let path = "/tmp/ffav-time-base-invalid.ts";
+        std::fs::write(path, b"recording").unwrap();
+        let mut v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        v_desc.time_base = AVRational::new(1, 0);
+        assert!(SimpleWriter::new(path, &[&v_desc], Some("mpegts"), None).is_err());
+        v_desc.time_base = AVRational::new(1, -25);
+        assert!(SimpleWriter::new(path, &[&v_desc], Some("mpegts"), None).is_err());
+        assert!(SimpleWriter::with_checksum(path, &[&v_desc], Some("mpegts"), None).is_err());
+        assert_eq!(std::fs::read(path).unwrap(), b"recording");
+    }
+
+    #[test]
+    fn test_simple_writer_faststart() {
+        let path = "/tmp/ffav-faststart.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer =
+            SimpleWriter::new(path, &[&v_desc], None, Some("movflags=faststart")).unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        let find = |tag: &[u8]| bytes.windows(4).position(|x| x == tag).unwrap();
+        assert!(find(b"moov") < find(b"mdat"));
+
+        assert!(SimpleWriter::new(
+            path,
+            &[&v_desc],
+            None,
+            Some("movflags=faststart+frag_keyframe")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_audio_desc() {
+        let a_desc = AudioDesc::with_aac(44100, 2, 128000);
+        assert_eq!(a_desc.codec_id, AV_CODEC_ID_AAC);
+        assert_eq!(a_desc.sample_fmt, AVSampleFormat::AV_SAMPLE_FMT_FLTP);
+        assert_eq!(a_desc.channel_layout, AV_CH_LAYOUT_STEREO);
+        let a_desc = AudioDesc::with_opus(48000, 1, 64000);
+        assert_eq!(a_desc.codec_id, AV_CODEC_ID_OPUS);
+        assert_eq!(a_desc.channel_layout, AV_CH_LAYOUT_MONO);
+        OpenOptions::new()
+            .media(a_desc)
+            .format("matroska")
+            .validate("/tmp/ffav-opus.mkv")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_open_options_validate() {
+        let _ = std::fs::remove_file("/tmp/ffav-validate.mp4");
+        let options = OpenOptions::new().media(VideoDesc::with_h264(352, 288, 4000, 1000000));
+        assert!(options.validate("/tmp/ffav-validate.mp4").is_ok());
+        assert!(!Path::new("/tmp/ffav-validate.mp4").exists());
+        assert!(options.validate("/tmp/ffav-validate.unknown").is_err());
+
+        let options = OpenOptions::new()
+            .media(VideoDesc::with_h265(352, 288, 4000, 1000000))
+            .format("flv");
+        assert!(options.validate("/tmp/ffav-validate").is_err());
+
+        // The medias without codec are skipped like `open()`.
+        let options = OpenOptions::new()
+            .media(AudioDesc::new())
+            .media(VideoDesc::with_h264(352, 288, 4000, 1000000));
+        assert!(options.validate("/tmp/ffav-validate.mp4").is_ok());
+        assert!(options.validate("/tmp/ffav-validate.mkv").is_ok());
+
+        let mut a_desc = AudioDesc::with_aac(48000, 2, 128000);
+        a_desc.time_base = AVRational::new(1, -1);
+        let options = OpenOptions::new().media(a_desc);
+        assert!(options.validate("/tmp/ffav-validate.mp4").is_err());
+
+        let options = OpenOptions::new()
+            .media(VideoDesc::with_h264(352, 288, 4000, 1000000))
+            .format_options("movflags=faststart+frag_keyframe");
+        assert!(options.validate("/tmp/ffav-validate.mp4").is_err());
+
+        let mut v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        v_desc.encoder_name = Some("png");
+        let options = OpenOptions::new().media(v_desc);
+        assert!(options.validate("/tmp/ffav-validate.mp4").is_err());
+    }
+
+    #[test]
+    fn test_rawvideo() {
+        let path = "/tmp/ffav-rawvideo.nut";
+        let v_desc =
+            VideoDesc::with_rawvideo(64, 48, AV_PIX_FMT_YUV420P, AVRational::new(25, 1), 1000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], Some("nut"), None).unwrap();
+        // Solid gray levels, the chroma planes are neutral.
+        for (i, &luma) in [16u8, 128, 235].iter().enumerate() {
+            let mut frame = vec![luma; 64 * 48];
+            frame.extend_from_slice(&[128u8; 32 * 24 * 2]);
+            writer
+                .write_bytes(&frame, i as i64 * 40, 40, false, 0)
+                .unwrap();
+        }
+        assert!(writer.write_bytes(&[0u8; 16], 120, 40, false, 0).is_err());
+        Box::new(writer).finish().unwrap();
+
+        let mut reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        let stream = reader.streams()[0];
+        assert_eq!(stream.r_frame_rate, AVRational::new(25, 1));
+        let par = stream.codecpar().unwrap();
+        assert_eq!(par.codec_id, AV_CODEC_ID_RAWVIDEO);
+        assert_eq!((par.width, par.height), (64, 48));
+        let mut lumas = vec![];
+        while let Some(packet) = reader.read_frame() {
+            assert_eq!(packet.data().len(), 64 * 48 * 3 / 2);
+            lumas.push(packet.data()[0]);
+        }
+        assert_eq!(lumas, vec![16, 128, 235]);
+    }
+
+    #[test]
+    fn test_mpegts_muxrate() {
+        let options = OpenOptions::new()
+            .format("mpegts")
+            .format_options("mpegts_copyts=1")
+            .mpegts_muxrate(4_000_000)
+            .pcr_period_ms(20);
+        assert_eq!(
+            options.muxer_options().as_deref(),
+            Some("mpegts_copyts=1:muxrate=4000000:pcr_period=20")
+        );
+        assert!(options.validate("/tmp/ffav-muxrate.ts").is_ok());
+        assert_eq!(OpenOptions::new().muxer_options(), None);
+        assert_eq!(
+            OpenOptions::new()
+                .flush_packets(true)
+                .muxer_options()
+                .as_deref(),
+            Some("fflags=+flush_packets")
+        );
+        assert_eq!(
+            OpenOptions::new()
+                .format_options("fflags=bitexact:mpegts_copyts=1")
+                .flush_packets(false)
+                .muxer_options()
+                .as_deref(),
+            Some("fflags=bitexact-flush_packets:mpegts_copyts=1")
+        );
+    }
+
+    #[test]
+    fn test_simple_writer_fix_dts() {
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let dts_list = Arc::new(Mutex::new(Vec::new()));
+        let dts_list_cloned = dts_list.clone();
+        let mut writer = OpenOptions::new()
+            .media(v_desc)
+            .format("mpegts")
+            .fix_dts(true)
+            .on_packet(move |pkt| dts_list_cloned.lock().unwrap().push(pkt.dts))
+            .open("/tmp/ffav-fix-dts.ts")
+            .unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            // Duplicate and backward timestamps.
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            writer
+                .write_bytes(frame_bytes, pts - 40000, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        writer.finish().unwrap();
+        let dts_list = dts_list.lock().unwrap();
+        assert!(!dts_list.is_empty());
+        assert!(dts_list.windows(2).all(|x| x[0] < x[1]));
+    }
+
+    #[test]
+    fn test_cover_art() {
+        // The 2x3 gray picture.
+        let png: &[u8] = &[
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x08, 0x00, 0x00, 0x00,
+            0x00, 0x9c, 0x81, 0x81, 0x5d, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x9c, 0x63, 0x68, 0x68, 0x60, 0x00, 0x23, 0x00, 0x0d, 0x89, 0x03, 0x01, 0x36, 0x1f,
+            0xf5, 0x2b, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+        assert_eq!(picture_size(png, AV_CODEC_ID_PNG), Some((2, 3)));
+        // SOI, APP0 of 4 bytes, SOF0 of 640x480.
+        let jpeg: &[u8] = &[
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, 0xff, 0xc0, 0x00, 0x11, 0x08, 0x01,
+            0xe0, 0x02, 0x80,
+        ];
+        assert_eq!(picture_size(jpeg, AV_CODEC_ID_MJPEG), Some((640, 480)));
+        assert_eq!(picture_size(&jpeg[..10], AV_CODEC_ID_MJPEG), None);
+        assert_eq!(picture_size(png, AV_CODEC_ID_MJPEG), None);
+
+        let path = "/tmp/ffav-cover-art.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = SimpleWriter::new(path, &[&v_desc], None, None).unwrap();
+        assert!(writer.set_cover_art(&png[..16], "image/png").is_err());
+        writer.set_cover_art(png, "image/png").unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames().take(25) {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, pts == 0, 0)
+                .unwrap();
+            pts += 40000;
+        }
+        Box::new(writer).finish().unwrap();
+
+        let reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        let stream = reader
+            .streams()
+            .iter()
+            .find(|x| (x.disposition & AV_DISPOSITION_ATTACHED_PIC) != 0)
+            .unwrap();
+        let par = stream.codecpar().unwrap();
+        assert_eq!(par.codec_id, AV_CODEC_ID_PNG);
+        assert_eq!((par.width, par.height), (2, 3));
+        assert_eq!(stream.attached_pic.size, png.len() as i32);
+    }
+
+    #[test]
+    fn test_simple_writer_timecode() {
+        let path = "/tmp/ffav-timecode.mp4";
+        let v_desc = VideoDesc::with_h264(352, 288, 4000, 1000000);
+        let mut writer = OpenOptions::new()
+            .media(v_desc)
+            .timecode("01:00:00:00", AVRational::new(25, 1))
+            .open(path)
+            .unwrap();
+        let mut pts = 0;
+        for frame_bytes in example_frames() {
+            writer
+                .write_bytes(frame_bytes, pts, 40000, false, 0)
+                .unwrap();
+            pts += 40000;
         }
+        writer.finish().unwrap();
+        let reader = crate::easy::SimpleReader::open(path, None, None).unwrap();
+        let timecode =
+            (0..reader.nb_streams()).find_map(|i| reader.stream_metadata(i).remove("timecode"));
+        assert_eq!(timecode.as_deref(), Some("01:00:00:00"));
+
+        assert!(check_timecode("01:00:00;29", AVRational::new(30000, 1001)).is_ok());
+        assert!(check_timecode("01:00:00:25", AVRational::new(25, 1)).is_err());
+        assert!(check_timecode("01;00:00:00", AVRational::new(25, 1)).is_err());
+        assert!(check_timecode("1:00:00:00", AVRational::new(25, 1)).is_err());
     }
 }